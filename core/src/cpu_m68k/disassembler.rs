@@ -43,6 +43,32 @@ impl std::fmt::Display for DisassemblyEntry {
     }
 }
 
+/// Disassembles a block of code, of which the first byte is located at address `base`.
+///
+/// Disassembly stops at an instruction that is truncated by the end of `bytes`; a
+/// partial instruction at the end of the block is not included in the output.
+///
+/// ```
+/// use snow_core::cpu_m68k::disassembler::disassemble_block;
+///
+/// // NOP, MOVEQ #1,D0, RTS and the first half of a NOP
+/// let code = [0x4E, 0x71, 0x70, 0x01, 0x4E, 0x75, 0x4E];
+/// let entries = disassemble_block(&code, 0x1000);
+///
+/// assert_eq!(entries.len(), 3);
+/// assert_eq!(entries[0].str, "NOP");
+/// assert_eq!(entries[1].addr, 0x1002);
+/// assert_eq!(entries[1].str, "MOVEQ #$01,D0");
+/// assert_eq!(entries[2].str, "RTS");
+///
+/// // JMP (xxx).L with the last word of the address missing
+/// assert!(disassemble_block(&[0x4E, 0xF9, 0x00, 0x40], 0).is_empty());
+/// ```
+pub fn disassemble_block(bytes: &[u8], base: Address) -> Vec<DisassemblyEntry> {
+    let mut iter = bytes.iter().copied();
+    Disassembler::from(&mut iter, base).collect()
+}
+
 pub struct Disassembler<'a> {
     /// Input iterator
     iter: &'a mut dyn Iterator<Item = u8>,
@@ -1,9 +1,17 @@
 //! Communication between emulator and frontend
 
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use snow_floppy::{FloppyImage, FloppyType};
+
 use crate::bus::Address;
 use crate::cpu_m68k::regs::RegisterFile;
-use crate::keymap::KeyEvent;
+use crate::emulator::condition::BreakCondition;
+use crate::keymap::{KeyEvent, Scancode};
 use crate::mac::MacModel;
+use crate::renderer::{DisplayAdjust, MonochromePalette};
 use crate::tickable::Ticks;
 
 pub type EmulatorCommandSender = crossbeam_channel::Sender<EmulatorCommand>;
@@ -13,7 +21,24 @@ pub type EmulatorEventReceiver = crossbeam_channel::Receiver<EmulatorEvent>;
 pub enum EmulatorCommand {
     Quit,
     InsertFloppy(usize, String),
+    /// Inserts an already loaded floppy image
+    InsertFloppyImage(usize, Box<FloppyImage>),
     SaveFloppy(usize, String),
+    SetFloppyWriteProtect(usize, bool),
+    /// Replaces the disk in a drive with a blank disk
+    FormatFloppy(usize, FloppyType),
+    /// Adjusts the spindle motor speed of a floppy drive (in rounds/minute)
+    SetFloppyRpmAdjustment(usize, i32),
+    /// Write protects a SCSI hard drive. Writes are refused and the image is not modified.
+    SetHddWriteProtect(usize, bool),
+    /// Starts a copy-on-write overlay on a SCSI hard drive, writes are kept in memory
+    StartHddOverlay(usize),
+    /// Writes the overlay of a SCSI hard drive to the disk image
+    CommitHddOverlay(usize),
+    /// Throws away the overlay of a SCSI hard drive
+    DiscardHddOverlay(usize),
+    /// Writes all pending hard drive changes to the disk images
+    FlushDisks,
     MouseUpdateAbsolute {
         x: u16,
         y: u16,
@@ -23,20 +48,127 @@ pub enum EmulatorCommand {
         rely: i16,
         btn: Option<bool>,
     },
+    /// Sets the scaling factor for relative mouse movement
+    SetMouseScale(f32),
     Run,
     Stop,
     Step,
     ToggleBreakpoint(Address),
+    /// Sets a breakpoint that only triggers when the condition is met.
+    /// Replaces the condition of an existing breakpoint on the same address.
+    SetConditionalBreakpoint(Address, BreakCondition),
+    /// Toggles a breakpoint on an A-line trap word (e.g. 0xA9C8 for _SysBeep).
+    /// Flag bits are ignored, so a breakpoint on a trap also fires on its variants.
+    ToggleTrapBreakpoint(u16),
+    /// Adds the watchpoint, or removes an existing watchpoint on the same address
+    ToggleWatchpoint(Watchpoint),
     BusWrite(Address, Vec<u8>),
     Disassemble(Address, usize),
     KeyEvent(KeyEvent),
+    /// Holds down the given keys (e.g. modifiers during boot) until the CPU cycle count
+    /// is reached. An empty list releases held keys.
+    HoldKeys(Vec<Scancode>, Ticks),
     ToggleBusTrace,
+    /// Presses the programmer's interrupt switch
+    ProgKey,
+    /// Runs the memory test of the ROM at boot, which is skipped by default.
+    /// Must be sent before the emulator is started.
+    EnableMemtest,
+    /// Writes every executed instruction to the given file (None to stop tracing)
+    SetInstructionTrace(Option<PathBuf>),
     CpuSetPC(u32),
     SetSpeed(EmulatorSpeed),
+    /// Sets the real-time clock
+    SetDateTime(NaiveDateTime),
+    /// Clears PRAM, it is reinitialized to defaults on the next boot
+    ResetPram,
+    /// Sets the startup disk in PRAM to the hard drive with the given SCSI ID,
+    /// or clears it (None) so the ROM searches for a startup disk
+    SetStartupDisk(Option<usize>),
+    /// Sets the audio output volume (0.0 - 1.0)
+    SetVolume(f32),
+    SetMuted(bool),
+    /// Enables/disables floppy drive sound effects
+    SetDriveSounds(bool),
+    /// Sets the brightness/contrast/gamma adjustment of the display output
+    SetDisplayAdjust(DisplayAdjust),
+    /// Sets the colors of the display output
+    SetMonochromePalette(MonochromePalette),
+    AddPatch {
+        addr: Address,
+        value: u32,
+        size: PatchSize,
+    },
+    RemovePatch(Address),
+    /// Searches RAM for a byte pattern. Bits cleared in the (optional) mask are ignored.
+    /// If `narrow` is set, only the results of the previous search are considered.
+    SearchMemory {
+        pattern: Vec<u8>,
+        mask: Option<Vec<u8>>,
+        narrow: bool,
+    },
+    /// Enables (or disables, with None) the hang watchdog
+    SetWatchdog(Option<Watchdog>),
+    /// Enables/disables sending GuestPowerEvent events. Detecting these needs a check
+    /// before every instruction, so they are disabled by default.
+    SetPowerEvents(bool),
 }
 
-/// Emulator speed tweak
+/// Watchdog that detects a hung guest
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Watchdog {
+    /// Emulated time without any change on screen and without the OS advancing
+    /// its Ticks counter after which the guest is considered hung
+    pub timeout: Duration,
+    /// Reset the machine when the watchdog triggers
+    pub reset: bool,
+}
+
+/// Access size of a memory patch
 #[derive(Debug, Copy, Clone, strum::Display, Eq, PartialEq)]
+pub enum PatchSize {
+    Byte,
+    Word,
+    Long,
+}
+
+impl PatchSize {
+    /// Size of the patch, in bytes
+    pub const fn byte_count(self) -> usize {
+        match self {
+            Self::Byte => 1,
+            Self::Word => 2,
+            Self::Long => 4,
+        }
+    }
+}
+
+/// A value pinned in memory, re-applied continuously while the emulator runs
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MemoryPatch {
+    pub addr: Address,
+    pub value: u32,
+    pub size: PatchSize,
+}
+
+impl MemoryPatch {
+    /// Gets the bytes to write to memory, big endian
+    pub fn bytes(&self) -> impl Iterator<Item = u8> {
+        let len = self.size.byte_count();
+        self.value.to_be_bytes().into_iter().skip(4 - len)
+    }
+}
+
+/// Memory access watchpoint, stops the emulator when the address is accessed
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Watchpoint {
+    pub addr: Address,
+    pub on_read: bool,
+    pub on_write: bool,
+}
+
+/// Emulator speed tweak
+#[derive(Debug, Copy, Clone, strum::Display, PartialEq)]
 pub enum EmulatorSpeed {
     /// Actual speed accurate to the real hardware
     Accurate,
@@ -46,6 +178,10 @@ pub enum EmulatorSpeed {
     Uncapped,
     /// Sync to 60 fps video, sound disabled
     Video,
+    /// Sync to video at the specified frames/second, sound disabled
+    VideoFps(u16),
+    /// Actual speed multiplied by a factor, sound disabled
+    Scaled(f32),
 }
 
 /// Structure with general emulator status
@@ -54,12 +190,22 @@ pub struct EmulatorStatus {
     pub regs: RegisterFile,
     pub running: bool,
     pub breakpoints: Vec<Address>,
+    /// Breakpoints on A-line traps, as trap words without flag bits
+    pub trap_breakpoints: Vec<u16>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub patches: Vec<MemoryPatch>,
     pub cycles: Ticks,
 
     pub fdd: [FddStatus; 3],
     pub model: MacModel,
     pub speed: EmulatorSpeed,
-    pub hdd: [Option<usize>; 7],
+    /// Instructions executed per second
+    pub ips: usize,
+    /// Current date/time of the real-time clock
+    pub datetime: NaiveDateTime,
+    pub volume: f32,
+    pub muted: bool,
+    pub hdd: [Option<HddStatus>; 7],
 }
 
 #[derive(Debug)]
@@ -68,13 +214,46 @@ pub struct FddStatus {
     pub ejected: bool,
     pub motor: bool,
     pub writing: bool,
+    pub writeprotect: bool,
     pub track: usize,
+    /// Current spindle motor speed, in rounds/minute
+    pub rpm: usize,
+    pub rpm_adjustment: i32,
     pub image_title: String,
 }
 
+#[derive(Debug)]
+pub struct HddStatus {
+    /// Capacity, in bytes
+    pub capacity: usize,
+    pub writeprotect: bool,
+    /// Amount of blocks in the copy-on-write overlay, None if there is no overlay
+    pub overlay: Option<usize>,
+    /// Writes have not been flushed to the disk image yet
+    pub dirty: bool,
+}
+
 /// A status message/event received from the emulator
 #[derive(Debug)]
 pub enum EmulatorEvent {
     Status(Box<EmulatorStatus>),
     NextCode((Address, Vec<u8>)),
+    /// Software attempted to write to a write protected floppy in the given drive
+    FloppyWriteProtected(usize),
+    SearchResults {
+        addresses: Vec<Address>,
+        /// More matches were found than fit in `addresses`
+        truncated: bool,
+    },
+    /// Software requested to shut down or restart the machine
+    GuestPowerEvent(PowerEvent),
+    /// The watchdog detected a hung guest
+    WatchdogTriggered,
+}
+
+/// Power state change requested by software
+#[derive(Debug, Copy, Clone, strum::Display, Eq, PartialEq)]
+pub enum PowerEvent {
+    Shutdown,
+    Restart,
 }
@@ -0,0 +1,288 @@
+//! Conditions for conditional breakpoints
+//!
+//! A condition compares two operands, e.g. `d0 == 5` or `[1F0].w != $FFFF`.
+//! Operands are:
+//!  * a data/address register (`d0`-`d7`, `a0`-`a7`), `sr` or `pc`
+//!  * memory: `[addr]` (byte), `[addr].w` (word) or `[addr].l` (long), the address in hex
+//!  * a constant, decimal or hex when prefixed with `$` or `0x`
+//!
+//! All values are compared unsigned.
+
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+
+use crate::bus::Address;
+use crate::cpu_m68k::regs::{Register, RegisterFile};
+use crate::types::Long;
+
+use super::comm::PatchSize;
+
+/// A value in a breakpoint condition
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Operand {
+    Register(Register),
+    Sr,
+    Pc,
+    /// Memory contents at an address (big endian)
+    Memory(Address, PatchSize),
+    Constant(u32),
+}
+
+impl Operand {
+    fn parse(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_lowercase();
+
+        if let Some(mem) = s.strip_prefix('[') {
+            let (addr, size) = mem.split_once(']').context("Missing ']'")?;
+            let size = match size {
+                "" | ".b" => PatchSize::Byte,
+                ".w" => PatchSize::Word,
+                ".l" => PatchSize::Long,
+                _ => bail!("Invalid memory access size: {}", size),
+            };
+            let addr = addr.trim().trim_start_matches('$').trim_start_matches("0x");
+            return Ok(Self::Memory(Address::from_str_radix(addr, 16)?, size));
+        }
+
+        match s.as_str() {
+            "sr" => return Ok(Self::Sr),
+            "pc" => return Ok(Self::Pc),
+            _ => (),
+        }
+        if let [r @ (b'd' | b'a'), n @ b'0'..=b'7'] = s.as_bytes() {
+            let n = usize::from(n - b'0');
+            return Ok(Self::Register(if *r == b'd' {
+                Register::Dn(n)
+            } else {
+                Register::An(n)
+            }));
+        }
+
+        let value = if let Some(hex) = s.strip_prefix('$').or_else(|| s.strip_prefix("0x")) {
+            u32::from_str_radix(hex, 16)
+        } else {
+            s.parse::<u32>()
+        };
+        Ok(Self::Constant(
+            value.with_context(|| format!("Invalid operand: {}", s))?,
+        ))
+    }
+
+    /// Gets the current value of the operand. Returns None if memory cannot be read.
+    fn value(
+        &self,
+        regs: &RegisterFile,
+        read: &mut impl FnMut(Address) -> Option<u8>,
+    ) -> Option<u32> {
+        match *self {
+            Self::Register(r) => Some(regs.read::<Long>(r)),
+            Self::Sr => Some(regs.sr.sr().into()),
+            Self::Pc => Some(regs.pc),
+            Self::Memory(addr, size) => (0..size.byte_count()).try_fold(0u32, |v, i| {
+                Some((v << 8) | u32::from(read(addr.wrapping_add(i as Address))?))
+            }),
+            Self::Constant(v) => Some(v),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register(r) => write!(f, "{}", r),
+            Self::Sr => write!(f, "SR"),
+            Self::Pc => write!(f, "PC"),
+            Self::Memory(addr, PatchSize::Byte) => write!(f, "[${:06X}]", addr),
+            Self::Memory(addr, PatchSize::Word) => write!(f, "[${:06X}].w", addr),
+            Self::Memory(addr, PatchSize::Long) => write!(f, "[${:06X}].l", addr),
+            Self::Constant(v) => write!(f, "${:X}", v),
+        }
+    }
+}
+
+/// Comparison operator in a breakpoint condition
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Comparison {
+    /// Operators, longest first so they are matched before their prefixes
+    const OPERATORS: [(&'static str, Self); 6] = [
+        ("==", Self::Equal),
+        ("!=", Self::NotEqual),
+        ("<=", Self::LessEqual),
+        (">=", Self::GreaterEqual),
+        ("<", Self::Less),
+        (">", Self::Greater),
+    ];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+        }
+    }
+
+    const fn compare(self, left: u32, right: u32) -> bool {
+        match self {
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+            Self::Less => left < right,
+            Self::LessEqual => left <= right,
+            Self::Greater => left > right,
+            Self::GreaterEqual => left >= right,
+        }
+    }
+}
+
+/// Condition of a conditional breakpoint
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BreakCondition {
+    pub left: Operand,
+    pub comparison: Comparison,
+    pub right: Operand,
+}
+
+impl BreakCondition {
+    /// Parses a condition, e.g. `d0 == 5`
+    pub fn parse(s: &str) -> Result<Self> {
+        let Some((pos, op, comparison)) = Comparison::OPERATORS
+            .iter()
+            .find_map(|&(op, cmp)| s.find(op).map(|pos| (pos, op, cmp)))
+        else {
+            bail!("Condition needs a comparison: ==, !=, <, <=, >, >=");
+        };
+
+        Ok(Self {
+            left: Operand::parse(&s[..pos])?,
+            comparison,
+            right: Operand::parse(&s[(pos + op.len())..])?,
+        })
+    }
+
+    /// Evaluates the condition. `read` reads a byte from memory without side effects.
+    /// A condition on memory that cannot be read is never met.
+    pub fn evaluate(
+        &self,
+        regs: &RegisterFile,
+        mut read: impl FnMut(Address) -> Option<u8>,
+    ) -> bool {
+        let (Some(left), Some(right)) = (
+            self.left.value(regs, &mut read),
+            self.right.value(regs, &mut read),
+        ) else {
+            return false;
+        };
+        self.comparison.compare(left, right)
+    }
+}
+
+impl fmt::Display for BreakCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.left,
+            self.comparison.as_str(),
+            self.right
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regs() -> RegisterFile {
+        let mut regs = RegisterFile::new();
+        regs.d[0] = 5;
+        regs.a[1] = 0x1000;
+        regs
+    }
+
+    fn mem(addr: Address) -> Option<u8> {
+        match addr {
+            0x1F0 => Some(0x12),
+            0x1F1 => Some(0x34),
+            0x1F2..=0x1F3 => Some(0),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            BreakCondition::parse("d0 == 5").unwrap(),
+            BreakCondition {
+                left: Operand::Register(Register::Dn(0)),
+                comparison: Comparison::Equal,
+                right: Operand::Constant(5),
+            }
+        );
+        assert_eq!(
+            BreakCondition::parse("[1F0].w>=$1234").unwrap(),
+            BreakCondition {
+                left: Operand::Memory(0x1F0, PatchSize::Word),
+                comparison: Comparison::GreaterEqual,
+                right: Operand::Constant(0x1234),
+            }
+        );
+        assert!(BreakCondition::parse("d0 5").is_err());
+        assert!(BreakCondition::parse("d8 == 5").is_err());
+        assert!(BreakCondition::parse("[1F0].q == 5").is_err());
+    }
+
+    #[test]
+    fn register() {
+        let regs = regs();
+        assert!(BreakCondition::parse("d0 == 5")
+            .unwrap()
+            .evaluate(&regs, mem));
+        assert!(!BreakCondition::parse("d0 != 5")
+            .unwrap()
+            .evaluate(&regs, mem));
+        assert!(BreakCondition::parse("a1 > d0")
+            .unwrap()
+            .evaluate(&regs, mem));
+        assert!(BreakCondition::parse("D1 < 1")
+            .unwrap()
+            .evaluate(&regs, mem));
+    }
+
+    #[test]
+    fn memory() {
+        let regs = regs();
+        assert!(BreakCondition::parse("[1F0] == $12")
+            .unwrap()
+            .evaluate(&regs, mem));
+        assert!(BreakCondition::parse("[1F0].w == 0x1234")
+            .unwrap()
+            .evaluate(&regs, mem));
+        assert!(BreakCondition::parse("[$1F0].l == $12340000")
+            .unwrap()
+            .evaluate(&regs, mem));
+        assert!(BreakCondition::parse("[1F1] != 0")
+            .unwrap()
+            .evaluate(&regs, mem));
+
+        // Unreadable memory never matches
+        assert!(!BreakCondition::parse("[800000] == 0")
+            .unwrap()
+            .evaluate(&regs, mem));
+        // Reads at the end of the address space wrap around instead of overflowing
+        assert!(!BreakCondition::parse("[FFFFFFFF].l == 0")
+            .unwrap()
+            .evaluate(&regs, mem));
+    }
+}
@@ -1,13 +1,22 @@
 pub mod comm;
+pub mod condition;
 
 use snow_floppy::loaders::{Autodetect, Bitfile, FloppyImageLoader, FloppyImageSaver};
 use snow_floppy::Floppy;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
 use std::thread;
 use std::time::{Duration, Instant};
 
 use crate::bus::{Address, Bus, InspectableBus};
 use crate::cpu_m68k::cpu::CpuM68k;
-use crate::keymap::Keymap;
+use crate::cpu_m68k::disassembler::Disassembler;
+use crate::cpu_m68k::regs::RegisterFile;
+use crate::keymap::{KeyEvent, Keymap, Scancode};
 use crate::mac::adb::{AdbKeyboard, AdbMouse};
 use crate::mac::audio::AudioReceiver;
 use crate::mac::bus::MacBus;
@@ -15,16 +24,96 @@ use crate::mac::video::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use crate::mac::MacModel;
 use crate::renderer::channel::ChannelRenderer;
 use crate::renderer::{DisplayBuffer, Renderer};
-use crate::tickable::{Tickable, Ticks};
+use crate::tickable::{Tickable, Ticks, TICKS_PER_SECOND};
 use crate::types::{ClickEventSender, KeyEventSender};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use log::*;
 
 use comm::{
     EmulatorCommand, EmulatorCommandSender, EmulatorEvent, EmulatorEventReceiver, EmulatorStatus,
-    FddStatus,
+    FddStatus, HddStatus, MemoryPatch, PatchSize, PowerEvent, Watchdog,
 };
+use condition::BreakCondition;
+
+/// Maximum amount of addresses reported back from a memory search
+const SEARCH_MAX_RESULTS: usize = 1000;
+
+/// _ShutDown trap word
+const TRAP_SHUTDOWN: u16 = 0xA895;
+/// _ShutDown selector to power off (ShutDwnPower)
+const SHUTDOWN_POWER: u16 = 1;
+/// _ShutDown selector to restart (ShutDwnStart)
+const SHUTDOWN_START: u16 = 2;
+
+/// Tests if `pattern` matches `data` at `offset`, ignoring bits that are cleared in `mask`.
+fn pattern_matches(data: &[u8], offset: usize, pattern: &[u8], mask: Option<&[u8]>) -> bool {
+    if offset + pattern.len() > data.len() {
+        return false;
+    }
+    pattern.iter().enumerate().all(|(i, &p)| {
+        let m = mask.and_then(|m| m.get(i).copied()).unwrap_or(0xFF);
+        data[offset + i] & m == p & m
+    })
+}
+
+/// Normalizes an A-line trap word by clearing its flag bits, so e.g. `_NewPtr,Sys`
+/// matches a breakpoint on `_NewPtr`. Returns None if the word is not an A-line trap.
+fn trap_normalize(opcode: u16) -> Option<u16> {
+    if opcode & 0xF000 != 0xA000 {
+        None
+    } else if opcode & 0x0800 != 0 {
+        // Toolbox trap, bit 10 is auto-pop
+        Some(opcode & !0x0400)
+    } else {
+        // OS trap, bits 8-10 are flags
+        Some(opcode & !0x0700)
+    }
+}
+
+/// Formats the registers that differ between two register files
+fn register_delta(before: &RegisterFile, after: &RegisterFile) -> String {
+    let mut out = String::new();
+    for (i, (b, a)) in before.d.iter().zip(after.d.iter()).enumerate() {
+        if b != a {
+            let _ = write!(out, " D{}={:08X}", i, a);
+        }
+    }
+    for (i, (b, a)) in before.a.iter().zip(after.a.iter()).enumerate() {
+        if b != a {
+            let _ = write!(out, " A{}={:08X}", i, a);
+        }
+    }
+    if before.usp != after.usp {
+        let _ = write!(out, " USP={:08X}", after.usp);
+    }
+    if before.ssp != after.ssp {
+        let _ = write!(out, " SSP={:08X}", after.ssp);
+    }
+    if before.sr != after.sr {
+        let _ = write!(out, " SR={:04X}", after.sr.sr());
+    }
+    out
+}
+
+/// A condition to stop a headless run at (see [`Emulator::run_until`])
+pub enum RunUntil {
+    /// Total CPU cycle count reached
+    Cycles(Ticks),
+    /// A frame received from the frame receiver equals the given control frame
+    /// (in display buffer format, 4 bytes per pixel)
+    Frame(crossbeam_channel::Receiver<DisplayBuffer>, Vec<u8>),
+    /// A breakpoint was hit
+    Breakpoint,
+}
+
+/// Reason a headless run stopped
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StopReason {
+    Cycles,
+    Frame,
+    Breakpoint,
+}
 
 /// Emulator runner
 pub struct Emulator {
@@ -34,10 +123,37 @@ pub struct Emulator {
     event_sender: crossbeam_channel::Sender<EmulatorEvent>,
     event_recv: EmulatorEventReceiver,
     run: bool,
+    /// At least one instruction has been executed
+    started: bool,
     breakpoints: Vec<Address>,
+    /// Conditions of conditional breakpoints, by address
+    breakpoint_conditions: HashMap<Address, BreakCondition>,
+    /// Breakpoints on A-line traps (normalized trap words)
+    trap_breakpoints: Vec<u16>,
+    watchdog: Option<Watchdog>,
+    /// Send GuestPowerEvent events
+    power_events: bool,
+    /// Cycle count at the last change of the screen contents or Ticks, for the watchdog
+    watchdog_last_change: Ticks,
+    /// Ticks low-memory global at the last watchdog check
+    watchdog_ticks: u32,
+    patches: Vec<MemoryPatch>,
+    search_results: Vec<Address>,
     last_update: Instant,
+    /// Instructions executed since last instructions/second measurement
+    ips_instructions: usize,
+    /// Time of last instructions/second measurement
+    ips_time: Instant,
+    /// Last measured instructions/second
+    ips: usize,
+    /// Instruction trace output, if enabled
+    instruction_trace: Option<BufWriter<File>>,
     adbmouse_sender: Option<ClickEventSender>,
     adbkeyboard_sender: Option<KeyEventSender>,
+    /// Keys held down by HoldKeys and the CPU cycle count to release them at
+    held_keys: Vec<Scancode>,
+    held_keys_until: Ticks,
+    held_keys_down: bool,
     model: MacModel,
 }
 
@@ -86,10 +202,26 @@ impl Emulator {
             event_sender: statuss,
             event_recv: statusr,
             run: false,
+            started: false,
             breakpoints: vec![],
+            breakpoint_conditions: HashMap::new(),
+            trap_breakpoints: vec![],
+            watchdog: None,
+            power_events: false,
+            watchdog_last_change: 0,
+            watchdog_ticks: 0,
+            patches: vec![],
+            search_results: vec![],
             last_update: Instant::now(),
+            ips_instructions: 0,
+            ips_time: Instant::now(),
+            ips: 0,
+            instruction_trace: None,
             adbmouse_sender,
             adbkeyboard_sender,
+            held_keys: vec![],
+            held_keys_until: 0,
+            held_keys_down: false,
             model,
         };
         emu.status_update()?;
@@ -106,23 +238,57 @@ impl Emulator {
     }
 
     fn status_update(&mut self) -> Result<()> {
+        // Measure instruction throughput, but not over too short periods
+        // (when updates are triggered by commands) to keep it stable.
+        let elapsed = self.ips_time.elapsed();
+        if !self.run {
+            self.ips = 0;
+            self.ips_instructions = 0;
+            self.ips_time = Instant::now();
+        } else if elapsed >= Duration::from_millis(250) {
+            self.ips = (self.ips_instructions as f64 / elapsed.as_secs_f64()) as usize;
+            self.ips_instructions = 0;
+            self.ips_time = Instant::now();
+        }
+
         self.event_sender
             .send(EmulatorEvent::Status(Box::new(EmulatorStatus {
                 regs: self.cpu.regs.clone(),
                 running: self.run,
                 breakpoints: self.breakpoints.clone(),
+                trap_breakpoints: self.trap_breakpoints.clone(),
+                watchpoints: self.cpu.bus.watchpoints.clone(),
+                patches: self.patches.clone(),
                 cycles: self.cpu.cycles,
                 fdd: core::array::from_fn(|i| FddStatus {
                     present: self.cpu.bus.swim.drives[i].is_present(),
                     ejected: !self.cpu.bus.swim.drives[i].floppy_inserted,
                     motor: self.cpu.bus.swim.drives[i].motor,
                     writing: self.cpu.bus.swim.drives[i].motor && self.cpu.bus.swim.is_writing(),
+                    writeprotect: self.cpu.bus.swim.drives[i].floppy.get_write_protect(),
                     track: self.cpu.bus.swim.drives[i].track,
+                    rpm: if self.cpu.bus.swim.drives[i].motor {
+                        self.cpu.bus.swim.drives[i].get_track_rpm()
+                    } else {
+                        0
+                    },
+                    rpm_adjustment: self.cpu.bus.swim.drives[i].get_rpm_adjustment(),
                     image_title: self.cpu.bus.swim.drives[i].floppy.get_title().to_owned(),
                 }),
                 model: self.model,
-                hdd: core::array::from_fn(|i| self.cpu.bus.scsi.get_disk_capacity(i)),
+                hdd: core::array::from_fn(|i| {
+                    Some(HddStatus {
+                        capacity: self.cpu.bus.scsi.get_disk_capacity(i)?,
+                        writeprotect: self.cpu.bus.scsi.get_write_protect(i),
+                        overlay: self.cpu.bus.scsi.get_overlay_blocks(i),
+                        dirty: self.cpu.bus.scsi.is_dirty(i),
+                    })
+                }),
                 speed: self.cpu.bus.speed,
+                ips: self.ips,
+                datetime: self.cpu.bus.via.rtc.get_datetime(),
+                volume: self.cpu.bus.audio.get_volume(),
+                muted: self.cpu.bus.audio.is_muted(),
             })))?;
 
         // Next code stream for disassembly listing
@@ -143,12 +309,183 @@ impl Emulator {
         Ok(())
     }
 
+    /// Sends a keyboard event to the keyboard of the emulated model
+    fn key_event(&mut self, e: KeyEvent) -> Result<()> {
+        if let Some(sender) = self.adbkeyboard_sender.as_ref() {
+            if let Some(e) = e.translate_scancode(Keymap::AekM0115) {
+                sender.send(e)?;
+            }
+        } else if let Some(e) = e.translate_scancode(Keymap::AkM0110) {
+            self.cpu.bus.via.keyboard.event(e)?;
+        }
+        Ok(())
+    }
+
+    /// Releases the keys held down by HoldKeys
+    fn release_held_keys(&mut self) -> Result<()> {
+        for sc in std::mem::take(&mut self.held_keys) {
+            self.key_event(KeyEvent::KeyUp(sc))?;
+        }
+        self.held_keys_down = false;
+        info!("Released held keys");
+        Ok(())
+    }
+
+    /// Disassembles a single instruction at the given address, for tracing
+    fn disassemble_one(&mut self, addr: Address) -> String {
+        let ops = (addr..)
+            .flat_map(|addr| self.cpu.bus.inspect_read(addr))
+            .take(12)
+            .collect::<Vec<_>>();
+        let mut iter = ops.into_iter();
+        Disassembler::from(&mut iter, addr)
+            .next()
+            .map_or_else(|| format!(":{:06X} ???", addr), |e| e.to_string())
+    }
+
+    /// Starts or stops writing the instruction trace file
+    fn set_instruction_trace(&mut self, filename: Option<&Path>) {
+        if let Some(mut trace) = self.instruction_trace.take() {
+            if let Err(e) = trace.flush() {
+                error!("Cannot write instruction trace: {}", e);
+            }
+            info!("Instruction trace stopped");
+        }
+        let Some(filename) = filename else {
+            return;
+        };
+        match File::create(filename) {
+            Ok(f) => {
+                info!("Writing instruction trace to {}", filename.display());
+                self.instruction_trace = Some(BufWriter::new(f));
+            }
+            Err(e) => error!("Cannot create {}: {}", filename.display(), e),
+        }
+    }
+
+    /// Writes an executed instruction and the registers it changed to the trace file
+    fn trace_instruction(&mut self, disasm: &str, before: &RegisterFile) {
+        let Some(trace) = self.instruction_trace.as_mut() else {
+            return;
+        };
+        let line = format!("{:<48}{}", disasm, register_delta(before, &self.cpu.regs));
+        if let Err(e) = writeln!(trace, "{}", line.trim_end()) {
+            error!("Cannot write instruction trace, stopping trace: {}", e);
+            self.instruction_trace = None;
+        }
+    }
+
+    /// Tests the condition of the breakpoint at the current PC, if any.
+    fn breakpoint_condition_met(&mut self) -> bool {
+        let Some(condition) = self.breakpoint_conditions.get(&self.cpu.regs.pc) else {
+            return true;
+        };
+        let cpu = &mut self.cpu;
+        condition.evaluate(&cpu.regs, |addr| cpu.bus.inspect_read(addr))
+    }
+
+    /// Reads a word from memory without side effects
+    fn inspect_word(&mut self, addr: Address) -> Option<u16> {
+        Some(u16::from_be_bytes([
+            self.cpu.bus.inspect_read(addr)?,
+            self.cpu.bus.inspect_read(addr.wrapping_add(1))?,
+        ]))
+    }
+
+    /// Tests if the instruction at the current PC is an A-line trap with a breakpoint.
+    fn trap_breakpoint_hit(&mut self) -> bool {
+        if self.trap_breakpoints.is_empty() {
+            return false;
+        }
+        self.inspect_word(self.cpu.regs.pc)
+            .and_then(trap_normalize)
+            .is_some_and(|trap| self.trap_breakpoints.contains(&trap))
+    }
+
+    /// Resets the machine, like pressing the reset switch
+    fn reset(&mut self) -> Result<()> {
+        self.cpu.bus.reset()?;
+        self.cpu.reset()?;
+        self.watchdog_last_change = self.cpu.cycles;
+        Ok(())
+    }
+
+    /// Checks if the guest made progress since the last check and, if it didn't for
+    /// the configured time, triggers the watchdog.
+    fn watchdog_check(&mut self) -> Result<()> {
+        let Some(watchdog) = self.watchdog else {
+            return Ok(());
+        };
+        // The guest is considered alive while either the screen changes or the OS
+        // keeps counting ticks
+        let fb_change = self.cpu.bus.video.get_clr_fb_change();
+        let ticks = self.cpu.bus.get_ticks();
+        if fb_change || ticks != self.watchdog_ticks {
+            self.watchdog_ticks = ticks;
+            self.watchdog_last_change = self.cpu.cycles;
+            return Ok(());
+        }
+
+        let timeout = watchdog.timeout.as_millis() as Ticks * TICKS_PER_SECOND / 1000;
+        if self.cpu.cycles.saturating_sub(self.watchdog_last_change) < timeout {
+            return Ok(());
+        }
+
+        warn!(
+            "Watchdog: no screen changes or ticks for {} seconds",
+            watchdog.timeout.as_secs()
+        );
+        self.event_sender.send(EmulatorEvent::WatchdogTriggered)?;
+        if watchdog.reset {
+            info!("Watchdog: resetting machine");
+            self.reset()?;
+        }
+        self.watchdog_last_change = self.cpu.cycles;
+        Ok(())
+    }
+
+    /// Tests if the instruction at the current PC is a _ShutDown call to power off
+    /// or restart the machine.
+    fn guest_power_event(&mut self) -> Option<PowerEvent> {
+        if !self.power_events {
+            return None;
+        }
+        // Quick reject on the high byte of the trap word (with or without auto-pop)
+        let pc = self.cpu.regs.pc;
+        if self.cpu.bus.inspect_read(pc)? & !0x04 != (TRAP_SHUTDOWN >> 8) as u8 {
+            return None;
+        }
+        if trap_normalize(self.inspect_word(pc)?)? != TRAP_SHUTDOWN {
+            return None;
+        }
+        // The selector is on top of the stack
+        match self.inspect_word(self.cpu.regs.read_a(7))? {
+            SHUTDOWN_POWER => Some(PowerEvent::Shutdown),
+            SHUTDOWN_START => Some(PowerEvent::Restart),
+            _ => None,
+        }
+    }
+
     /// Steps the emulator by one instruction.
     fn step(&mut self) -> Result<()> {
         let mut stop_break = false;
+        self.started = true;
         self.cpu.bus.swim.dbg_pc = self.cpu.regs.pc;
         self.cpu.bus.scsi.dbg_pc = self.cpu.regs.pc;
-        self.cpu.tick(1)?;
+        if let Some(event) = self.guest_power_event() {
+            info!("Guest power event: {}", event);
+            self.event_sender
+                .send(EmulatorEvent::GuestPowerEvent(event))?;
+        }
+        if self.instruction_trace.is_some() {
+            let before = self.cpu.regs.clone();
+            let disasm = self.disassemble_one(before.pc);
+            self.cpu.tick(1)?;
+            self.trace_instruction(&disasm, &before);
+        } else {
+            self.cpu.tick(1)?;
+        }
+        self.ips_instructions += 1;
 
         // Mac 512K: 0x402154, Mac Plus: 0x418CCC
         //if self.cpu.regs.pc == 0x418CCC {
@@ -165,7 +502,8 @@ impl Emulator {
         //}
 
         if self.run
-            && (self.breakpoints.contains(&self.cpu.regs.pc)
+            && ((self.breakpoints.contains(&self.cpu.regs.pc) && self.breakpoint_condition_met())
+                || self.trap_breakpoint_hit()
                 || self.cpu.bus.swim.dbg_break.get_clear()
                 || self.cpu.bus.dbg_break.get_clear())
         {
@@ -179,6 +517,100 @@ impl Emulator {
         Ok(())
     }
 
+    /// Searches RAM for a pattern and reports the matching addresses to the frontend
+    fn search_memory(&mut self, pattern: &[u8], mask: Option<&[u8]>, narrow: bool) -> Result<()> {
+        let ram = &self.cpu.bus.ram;
+        if pattern.is_empty() || pattern.len() > ram.len() {
+            error!("Invalid search pattern length: {}", pattern.len());
+            return Ok(());
+        }
+
+        self.search_results = if narrow {
+            self.search_results
+                .iter()
+                .copied()
+                .filter(|&a| pattern_matches(ram, a as usize, pattern, mask))
+                .collect()
+        } else {
+            (0..=(ram.len() - pattern.len()))
+                .filter(|&a| pattern_matches(ram, a, pattern, mask))
+                .map(|a| a as Address)
+                .collect()
+        };
+        info!("Memory search: {} match(es)", self.search_results.len());
+
+        self.event_sender.send(EmulatorEvent::SearchResults {
+            addresses: self
+                .search_results
+                .iter()
+                .copied()
+                .take(SEARCH_MAX_RESULTS)
+                .collect(),
+            truncated: self.search_results.len() > SEARCH_MAX_RESULTS,
+        })?;
+        Ok(())
+    }
+
+    /// Writes all active memory patches to memory
+    fn apply_patches(&mut self) {
+        for patch in &self.patches {
+            for (i, b) in patch.bytes().enumerate() {
+                self.cpu.bus.inspect_write(patch.addr + i as Address, b);
+            }
+        }
+    }
+
+    /// Runs the emulator until one of the given conditions is met and returns which.
+    ///
+    /// Intended for headless use: commands sent to the emulator are not processed
+    /// and no status updates are sent while this runs. Without any conditions that
+    /// can be met (e.g. only [`RunUntil::Breakpoint`] without breakpoints), this
+    /// never returns. An empty list of conditions is an error.
+    pub fn run_until(&mut self, conditions: &[RunUntil]) -> Result<StopReason> {
+        /// Amount of steps between checking for new frames
+        const FRAME_CHECK_STEPS: usize = 1000;
+
+        if conditions.is_empty() {
+            bail!("No conditions to stop at");
+        }
+
+        self.run = true;
+        let mut steps = 0;
+        loop {
+            self.step()?;
+            steps += 1;
+
+            for condition in conditions {
+                match condition {
+                    RunUntil::Cycles(c) if self.cpu.cycles >= *c => {
+                        self.run = false;
+                        return Ok(StopReason::Cycles);
+                    }
+                    RunUntil::Breakpoint if !self.run => {
+                        return Ok(StopReason::Breakpoint);
+                    }
+                    RunUntil::Frame(recv, control) if steps % FRAME_CHECK_STEPS == 0 => {
+                        while let Ok(frame) = recv.try_recv() {
+                            if frame.len() == control.len()
+                                && frame
+                                    .iter()
+                                    .zip(control)
+                                    .all(|(a, &b)| a.load(Ordering::Acquire) == b)
+                            {
+                                self.run = false;
+                                return Ok(StopReason::Frame);
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            // Keep going if a breakpoint was hit but is not a stop condition
+            self.run = true;
+        }
+    }
+
     pub fn get_audio(&self) -> AudioReceiver {
         self.cpu.bus.get_audio_channel()
     }
@@ -200,10 +632,23 @@ impl Tickable for Emulator {
                     EmulatorCommand::MouseUpdateAbsolute { x, y } => {
                         self.cpu.bus.mouse_update_abs(x, y);
                     }
+                    EmulatorCommand::SetMouseScale(scale) => {
+                        self.cpu.bus.set_mouse_scale(scale);
+                    }
                     EmulatorCommand::Quit => {
                         info!("Emulator terminating");
+                        if let Err(e) = self.cpu.bus.scsi.flush() {
+                            error!("Cannot write hard drive changes to disk: {}", e);
+                        }
                         return Ok(0);
                     }
+                    EmulatorCommand::FlushDisks => {
+                        match self.cpu.bus.scsi.flush() {
+                            Ok(()) => info!("Hard drive changes written to disk"),
+                            Err(e) => error!("Cannot write hard drive changes to disk: {}", e),
+                        }
+                        self.status_update()?;
+                    }
                     EmulatorCommand::InsertFloppy(drive, filename) => {
                         let image = Autodetect::load_file(&filename);
                         match image {
@@ -216,10 +661,77 @@ impl Tickable for Emulator {
                         }
                         self.status_update()?;
                     }
+                    EmulatorCommand::InsertFloppyImage(drive, img) => {
+                        if let Err(e) = self.cpu.bus.swim.disk_insert(drive, *img) {
+                            error!("Cannot insert disk: {}", e);
+                        }
+                        self.status_update()?;
+                    }
                     EmulatorCommand::SaveFloppy(drive, filename) => {
                         Bitfile::save_file(self.cpu.bus.swim.get_active_image(drive), &filename)?;
                         self.status_update()?;
                     }
+                    EmulatorCommand::FormatFloppy(drive, floppy_type) => {
+                        match self.cpu.bus.swim.disk_format(drive, floppy_type) {
+                            Ok(()) => info!("Drive {}: inserted blank {} disk", drive, floppy_type),
+                            Err(e) => error!("Cannot format disk: {}", e),
+                        }
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::SetFloppyWriteProtect(drive, wp) => {
+                        match self.cpu.bus.swim.set_write_protect(drive, wp) {
+                            Ok(()) => info!(
+                                "Drive {}: write protect {}",
+                                drive,
+                                if wp { "on" } else { "off" }
+                            ),
+                            Err(e) => error!("Cannot change write protect: {}", e),
+                        }
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::SetHddWriteProtect(id, wp) => {
+                        match self.cpu.bus.scsi.set_write_protect(id, wp) {
+                            Ok(()) => info!(
+                                "SCSI ID {}: write protect {}",
+                                id,
+                                if wp { "on" } else { "off" }
+                            ),
+                            Err(e) => error!("Cannot change write protect: {}", e),
+                        }
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::StartHddOverlay(id) => {
+                        match self.cpu.bus.scsi.overlay_start(id) {
+                            Ok(()) => info!("SCSI ID {}: overlay started", id),
+                            Err(e) => error!("Cannot start overlay: {}", e),
+                        }
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::CommitHddOverlay(id) => {
+                        match self.cpu.bus.scsi.overlay_commit(id) {
+                            Ok(blocks) => info!("SCSI ID {}: {} blocks committed", id, blocks),
+                            Err(e) => error!("Cannot commit overlay: {}", e),
+                        }
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::DiscardHddOverlay(id) => {
+                        match self.cpu.bus.scsi.overlay_discard(id) {
+                            Ok(blocks) => info!("SCSI ID {}: {} blocks discarded", id, blocks),
+                            Err(e) => error!("Cannot discard overlay: {}", e),
+                        }
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::SetFloppyRpmAdjustment(drive, adjustment) => {
+                        match self.cpu.bus.swim.set_rpm_adjustment(drive, adjustment) {
+                            Ok(()) => info!(
+                                "Drive {}: RPM adjustment {:+}",
+                                drive,
+                                self.cpu.bus.swim.drives[drive].get_rpm_adjustment()
+                            ),
+                            Err(e) => error!("Cannot adjust RPM: {}", e),
+                        }
+                        self.status_update()?;
+                    }
                     EmulatorCommand::Run => {
                         info!("Running");
                         self.run = true;
@@ -239,6 +751,7 @@ impl Tickable for Emulator {
                     EmulatorCommand::ToggleBreakpoint(addr) => {
                         if let Some(idx) = self.breakpoints.iter().position(|&v| v == addr) {
                             self.breakpoints.remove(idx);
+                            self.breakpoint_conditions.remove(&addr);
                             info!("Breakpoint removed: ${:06X}", addr);
                         } else {
                             self.breakpoints.push(addr);
@@ -246,6 +759,40 @@ impl Tickable for Emulator {
                         }
                         self.status_update()?;
                     }
+                    EmulatorCommand::SetConditionalBreakpoint(addr, condition) => {
+                        if !self.breakpoints.contains(&addr) {
+                            self.breakpoints.push(addr);
+                        }
+                        self.breakpoint_conditions.insert(addr, condition);
+                        info!("Breakpoint set: ${:06X} if {}", addr, condition);
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::ToggleTrapBreakpoint(opcode) => {
+                        if let Some(trap) = trap_normalize(opcode) {
+                            if let Some(idx) = self.trap_breakpoints.iter().position(|&t| t == trap)
+                            {
+                                self.trap_breakpoints.remove(idx);
+                                info!("Trap breakpoint removed: ${:04X}", trap);
+                            } else {
+                                self.trap_breakpoints.push(trap);
+                                info!("Trap breakpoint set: ${:04X}", trap);
+                            }
+                            self.status_update()?;
+                        } else {
+                            error!("Not an A-line trap: ${:04X}", opcode);
+                        }
+                    }
+                    EmulatorCommand::ToggleWatchpoint(wp) => {
+                        let watchpoints = &mut self.cpu.bus.watchpoints;
+                        if let Some(idx) = watchpoints.iter().position(|w| w.addr == wp.addr) {
+                            watchpoints.remove(idx);
+                            info!("Watchpoint removed: ${:06X}", wp.addr);
+                        } else {
+                            watchpoints.push(wp);
+                            info!("Watchpoint set: ${:06X}", wp.addr);
+                        }
+                        self.status_update()?;
+                    }
                     EmulatorCommand::BusWrite(start, data) => {
                         for (i, d) in data.into_iter().enumerate() {
                             self.cpu.bus.write(start + (i as Address), d);
@@ -260,17 +807,117 @@ impl Tickable for Emulator {
                     EmulatorCommand::KeyEvent(e) => {
                         if !self.run {
                             info!("Ignoring keyboard input while stopped");
-                        } else if let Some(sender) = self.adbkeyboard_sender.as_ref() {
-                            if let Some(e) = e.translate_scancode(Keymap::AekM0115) {
-                                sender.send(e)?;
-                            }
-                        } else if let Some(e) = e.translate_scancode(Keymap::AkM0110) {
-                            self.cpu.bus.via.keyboard.event(e)?;
+                        } else {
+                            self.key_event(e)?;
+                        }
+                    }
+                    EmulatorCommand::HoldKeys(keys, until) => {
+                        if self.held_keys_down {
+                            self.release_held_keys()?;
                         }
+                        self.held_keys = keys;
+                        self.held_keys_until = until;
                     }
                     EmulatorCommand::ToggleBusTrace => self.cpu.bus.trace = !self.cpu.bus.trace,
+                    EmulatorCommand::ProgKey => self.cpu.bus.progkey(),
+                    EmulatorCommand::EnableMemtest => {
+                        // Resetting the CPU already takes cycles, so check for executed
+                        // instructions instead
+                        if self.started {
+                            error!("Memory test can only be enabled before starting the emulator");
+                        } else {
+                            self.cpu.bus.enable_memtest();
+                        }
+                    }
+                    EmulatorCommand::SetWatchdog(watchdog) => {
+                        if let Some(w) = watchdog {
+                            info!(
+                                "Watchdog enabled: {} seconds{}",
+                                w.timeout.as_secs(),
+                                if w.reset { ", auto-reset" } else { "" }
+                            );
+                        } else {
+                            info!("Watchdog disabled");
+                        }
+                        self.watchdog = watchdog;
+                        self.watchdog_last_change = self.cpu.cycles;
+                    }
+                    EmulatorCommand::SetPowerEvents(v) => self.power_events = v,
+                    EmulatorCommand::SetInstructionTrace(filename) => {
+                        self.set_instruction_trace(filename.as_deref());
+                    }
                     EmulatorCommand::CpuSetPC(val) => self.cpu.set_pc(val)?,
                     EmulatorCommand::SetSpeed(s) => self.cpu.bus.set_speed(s),
+                    EmulatorCommand::SetDateTime(dt) => {
+                        self.cpu.bus.via.rtc.set_datetime(dt);
+                        info!("Clock set to {}", dt);
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::ResetPram => self.cpu.bus.via.rtc.reset_pram(),
+                    EmulatorCommand::SetStartupDisk(None) => {
+                        self.cpu.bus.via.rtc.set_startup_scsi(None);
+                        info!("Startup disk cleared");
+                    }
+                    EmulatorCommand::SetStartupDisk(Some(id)) => {
+                        if !self.model.has_scsi() {
+                            warn!("Cannot set startup disk: {} has no SCSI", self.model);
+                        } else if id > 6 || self.cpu.bus.scsi.get_disk_capacity(id).is_none() {
+                            warn!("Cannot set startup disk: no hard drive on SCSI ID {}", id);
+                        } else {
+                            self.cpu.bus.via.rtc.set_startup_scsi(Some(id));
+                            info!("Startup disk set to SCSI ID {}", id);
+                        }
+                    }
+                    EmulatorCommand::SetVolume(v) => {
+                        self.cpu.bus.audio.set_volume(v);
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::SetMuted(m) => {
+                        self.cpu.bus.audio.set_muted(m);
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::SetDriveSounds(v) => {
+                        info!("Drive sounds {}", if v { "enabled" } else { "disabled" });
+                        self.cpu.bus.drive_sounds.enabled = v;
+                    }
+                    EmulatorCommand::SetDisplayAdjust(adjust) => {
+                        info!(
+                            "Display brightness {:+.2}, contrast {:.2}, gamma {:.2}",
+                            adjust.brightness, adjust.contrast, adjust.gamma
+                        );
+                        self.cpu.bus.video.set_adjust(adjust);
+                    }
+                    EmulatorCommand::SetMonochromePalette(palette) => {
+                        info!("Display palette: {}", palette);
+                        self.cpu.bus.video.set_palette(palette);
+                    }
+                    EmulatorCommand::AddPatch { addr, value, size } => {
+                        if size != PatchSize::Byte && addr & 1 != 0 {
+                            error!("Cannot patch {} at unaligned address ${:06X}", size, addr);
+                        } else if size.byte_count() < 4 && value >> (size.byte_count() * 8) != 0 {
+                            error!("Patch value ${:X} does not fit in a {}", value, size);
+                        } else {
+                            self.patches.retain(|p| p.addr != addr);
+                            self.patches.push(MemoryPatch { addr, value, size });
+                            self.apply_patches();
+                            info!("Patch set: ${:06X} = ${:X} ({})", addr, value, size);
+                        }
+                        self.status_update()?;
+                    }
+                    EmulatorCommand::SearchMemory {
+                        pattern,
+                        mask,
+                        narrow,
+                    } => {
+                        self.search_memory(&pattern, mask.as_deref(), narrow)?;
+                    }
+                    EmulatorCommand::RemovePatch(addr) => {
+                        if let Some(idx) = self.patches.iter().position(|p| p.addr == addr) {
+                            self.patches.remove(idx);
+                            info!("Patch removed: ${:06X}", addr);
+                        }
+                        self.status_update()?;
+                    }
                 }
             }
         }
@@ -281,6 +928,24 @@ impl Tickable for Emulator {
                 self.status_update()?;
             }
 
+            if !self.held_keys.is_empty() {
+                if self.held_keys_down && self.cpu.cycles >= self.held_keys_until {
+                    self.release_held_keys()?;
+                } else if !self.held_keys_down
+                    || self
+                        .adbkeyboard_sender
+                        .as_ref()
+                        .is_some_and(|s| s.is_empty())
+                {
+                    // The ROM resets the ADB keyboard during boot, which flushes its
+                    // queue, so keep sending the keys while the queue runs empty.
+                    for sc in self.held_keys.clone() {
+                        self.key_event(KeyEvent::KeyDown(sc))?;
+                    }
+                    self.held_keys_down = true;
+                }
+            }
+
             // Batch 10000 steps for performance reasons
             for _ in 0..10000 {
                 if !self.run {
@@ -288,6 +953,25 @@ impl Tickable for Emulator {
                 }
                 self.step()?;
             }
+
+            // Keep pinned values in place
+            self.apply_patches();
+
+            self.watchdog_check()?;
+
+            if let Some(trace) = self.instruction_trace.as_mut() {
+                if let Err(e) = trace.flush() {
+                    error!("Cannot write instruction trace, stopping trace: {}", e);
+                    self.instruction_trace = None;
+                }
+            }
+
+            for (drive, event) in self.cpu.bus.swim.wp_write_event.iter_mut().enumerate() {
+                if event.get_clear() {
+                    self.event_sender
+                        .send(EmulatorEvent::FloppyWriteProtected(drive))?;
+                }
+            }
         } else {
             thread::sleep(Duration::from_millis(100));
         }
@@ -295,3 +979,264 @@ impl Tickable for Emulator {
         Ok(ticks)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start of the test code in the ROM, after the reset vectors
+    const TEST_CODE: Address = 0x0040_0008;
+
+    /// Creates an emulator with a ROM that starts executing `code` from reset
+    fn test_emulator(
+        model: MacModel,
+        code: &[u8],
+    ) -> (Emulator, crossbeam_channel::Receiver<DisplayBuffer>) {
+        let mut rom = vec![0; 128 * 1024];
+        rom[0..4].copy_from_slice(&0x0060_1000_u32.to_be_bytes());
+        rom[4..8].copy_from_slice(&TEST_CODE.to_be_bytes());
+        rom[8..(8 + code.len())].copy_from_slice(code);
+        Emulator::new(&rom, model).unwrap()
+    }
+
+    #[test]
+    fn register_delta_changed() {
+        let before = RegisterFile::new();
+        let mut after = before.clone();
+        assert_eq!(register_delta(&before, &after), "");
+
+        after.d[1] = 0x1234;
+        after.a[6] = 0xFFFF_0000;
+        after.pc = 0x400;
+        assert_eq!(register_delta(&before, &after), " D1=00001234 A6=FFFF0000");
+    }
+
+    #[test]
+    fn trap_normalize_flags() {
+        // Not A-line
+        assert_eq!(trap_normalize(0x4E75), None);
+        // _SysBeep, with and without auto-pop
+        assert_eq!(trap_normalize(0xA9C8), Some(0xA9C8));
+        assert_eq!(trap_normalize(0xADC8), Some(0xA9C8));
+        // _NewPtr, _NewPtr,Sys and _NewPtr,Sys,Clear
+        assert_eq!(trap_normalize(0xA11E), Some(0xA01E));
+        assert_eq!(trap_normalize(0xA51E), Some(0xA01E));
+        assert_eq!(trap_normalize(0xA71E), Some(0xA01E));
+    }
+
+    #[test]
+    fn run_until_no_conditions() {
+        let (mut emu, _) = test_emulator(MacModel::Plus, &[0x60, 0xFE]);
+        assert!(emu.run_until(&[]).is_err());
+    }
+
+    #[test]
+    fn run_until_cycles() {
+        // BRA.S *
+        let (mut emu, _) = test_emulator(MacModel::Plus, &[0x60, 0xFE]);
+        assert_eq!(
+            emu.run_until(&[RunUntil::Breakpoint, RunUntil::Cycles(10000)])
+                .unwrap(),
+            StopReason::Cycles
+        );
+        assert!(emu.cpu.cycles >= 10000);
+        assert!(!emu.run);
+    }
+
+    #[test]
+    fn run_until_breakpoint() {
+        // NOP, NOP, BRA.S *
+        let (mut emu, _) = test_emulator(MacModel::Plus, &[0x4E, 0x71, 0x4E, 0x71, 0x60, 0xFE]);
+        emu.breakpoints.push(TEST_CODE + 2);
+        assert_eq!(
+            emu.run_until(&[RunUntil::Breakpoint, RunUntil::Cycles(10000)])
+                .unwrap(),
+            StopReason::Breakpoint
+        );
+        assert_eq!(emu.cpu.regs.pc, TEST_CODE + 2);
+    }
+
+    #[test]
+    fn run_until_frame() {
+        // BRA.S *, with the framebuffer left empty (white)
+        let (mut emu, frame_recv) = test_emulator(MacModel::Plus, &[0x60, 0xFE]);
+        let control = [0xFF, 0xFF, 0xFF, 0x00].repeat(SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert_eq!(
+            emu.run_until(&[
+                RunUntil::Frame(frame_recv, control),
+                RunUntil::Cycles(TICKS_PER_SECOND)
+            ])
+            .unwrap(),
+            StopReason::Frame
+        );
+    }
+
+    #[test]
+    fn trap_breakpoint() {
+        // NOP, _SysBeep with auto-pop, BRA.S *
+        let (mut emu, _) = test_emulator(MacModel::Plus, &[0x4E, 0x71, 0xAD, 0xC8, 0x60, 0xFE]);
+        emu.command_sender
+            .send(EmulatorCommand::ToggleTrapBreakpoint(0xA9C8))
+            .unwrap();
+        emu.command_sender.send(EmulatorCommand::Run).unwrap();
+        emu.tick(1).unwrap();
+
+        // Stopped before executing the trap
+        assert!(!emu.run);
+        assert_eq!(emu.cpu.regs.pc, TEST_CODE + 2);
+    }
+
+    /// Runs `_ShutDown` with the given selector and returns the power event sent, if any
+    fn shutdown_event(selector: u16, enable: bool) -> Option<PowerEvent> {
+        let [sel_hi, sel_lo] = selector.to_be_bytes();
+        // MOVE.W #selector,-(SP), _ShutDown
+        let (mut emu, _) = test_emulator(MacModel::Plus, &[0x3F, 0x3C, sel_hi, sel_lo, 0xA8, 0x95]);
+        emu.command_sender
+            .send(EmulatorCommand::SetPowerEvents(enable))
+            .unwrap();
+        emu.tick(1).unwrap();
+        emu.step().unwrap();
+        emu.step().unwrap();
+
+        emu.event_recv.try_iter().find_map(|e| match e {
+            EmulatorEvent::GuestPowerEvent(p) => Some(p),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn guest_power_event() {
+        assert_eq!(
+            shutdown_event(SHUTDOWN_POWER, true),
+            Some(PowerEvent::Shutdown)
+        );
+        assert_eq!(
+            shutdown_event(SHUTDOWN_START, true),
+            Some(PowerEvent::Restart)
+        );
+        // Other selectors (e.g. ShutDwnInstall) are not power events
+        assert_eq!(shutdown_event(3, true), None);
+        // Disabled by default
+        assert_eq!(shutdown_event(SHUTDOWN_POWER, false), None);
+    }
+
+    #[test]
+    fn watchdog_check() {
+        let (mut emu, _) = test_emulator(MacModel::Plus, &[0x60, 0xFE]);
+        emu.watchdog = Some(Watchdog {
+            timeout: Duration::from_secs(1),
+            reset: false,
+        });
+        let triggered = |emu: &Emulator| {
+            emu.event_recv
+                .try_iter()
+                .any(|e| matches!(e, EmulatorEvent::WatchdogTriggered))
+        };
+
+        // Not timed out yet
+        emu.cpu.cycles = TICKS_PER_SECOND / 2;
+        emu.watchdog_check().unwrap();
+        assert!(!triggered(&emu));
+
+        // Ticks advancing on an unchanged screen
+        emu.cpu.bus.ram[0x16D] = 1;
+        emu.cpu.cycles = TICKS_PER_SECOND * 2;
+        emu.watchdog_check().unwrap();
+        assert!(!triggered(&emu));
+
+        // Screen changing while Ticks stands still
+        emu.cpu.bus.video.write_framebuffer(0, 0, 0xFF);
+        emu.cpu.cycles = TICKS_PER_SECOND * 4;
+        emu.watchdog_check().unwrap();
+        assert!(!triggered(&emu));
+
+        // Both stalled
+        emu.cpu.cycles = TICKS_PER_SECOND * 6;
+        emu.watchdog_check().unwrap();
+        assert!(triggered(&emu));
+    }
+
+    #[test]
+    fn enable_memtest() {
+        let (addr, value) = MacModel::Plus.disable_memtest().unwrap();
+        let patch = addr as usize..(addr as usize + 4);
+
+        let (mut emu, _) = test_emulator(MacModel::Plus, &[0x60, 0xFE]);
+        assert_eq!(emu.cpu.bus.ram[patch.clone()], value.to_be_bytes());
+        emu.command_sender
+            .send(EmulatorCommand::EnableMemtest)
+            .unwrap();
+        emu.tick(1).unwrap();
+        assert_eq!(emu.cpu.bus.ram[patch], [0; 4]);
+    }
+
+    #[test]
+    fn enable_memtest_started() {
+        let (addr, value) = MacModel::Plus.disable_memtest().unwrap();
+        let patch = addr as usize..(addr as usize + 4);
+
+        let (mut emu, _) = test_emulator(MacModel::Plus, &[0x60, 0xFE]);
+        emu.step().unwrap();
+        emu.command_sender
+            .send(EmulatorCommand::EnableMemtest)
+            .unwrap();
+        emu.tick(1).unwrap();
+        assert_eq!(emu.cpu.bus.ram[patch], value.to_be_bytes());
+    }
+
+    #[test]
+    fn hold_keys_adb_reset() {
+        /// ADB Talk register 0 command for the keyboard
+        const KEYBOARD_TALK_R0: u8 = (AdbKeyboard::INITIAL_ADDRESS << 4) | 0b1100;
+        /// Shift key
+        const SHIFT: Scancode = 0x38;
+
+        let (mut emu, _) = test_emulator(MacModel::SE, &[0x60, 0xFE]);
+        emu.command_sender.send(EmulatorCommand::Run).unwrap();
+        emu.command_sender
+            .send(EmulatorCommand::HoldKeys(vec![SHIFT], TICKS_PER_SECOND))
+            .unwrap();
+        emu.tick(1).unwrap();
+
+        // ADB reset during boot flushes the key events
+        let adb = &mut emu.cpu.bus.via.adb;
+        adb.io(false, false);
+        adb.data_in(0x00);
+        adb.io(true, false);
+        adb.io(true, true);
+        emu.tick(1).unwrap();
+
+        // Shift is still reported down
+        let adb = &mut emu.cpu.bus.via.adb;
+        adb.io(false, false);
+        adb.data_in(KEYBOARD_TALK_R0);
+        assert_eq!(adb.io(true, false), Some(SHIFT));
+    }
+
+    #[test]
+    fn pattern_matches_exact() {
+        let data = [0x00, 0x12, 0x34, 0x56];
+        assert!(pattern_matches(&data, 1, &[0x12, 0x34], None));
+        assert!(pattern_matches(&data, 2, &[0x34, 0x56], None));
+        assert!(!pattern_matches(&data, 0, &[0x12, 0x34], None));
+        // Out of bounds
+        assert!(!pattern_matches(&data, 3, &[0x56, 0x00], None));
+    }
+
+    #[test]
+    fn pattern_matches_masked() {
+        let data = [0x00, 0x12, 0x34, 0x56];
+        assert!(pattern_matches(
+            &data,
+            1,
+            &[0x10, 0x00],
+            Some(&[0xF0, 0x00])
+        ));
+        assert!(!pattern_matches(
+            &data,
+            1,
+            &[0x20, 0x00],
+            Some(&[0xF0, 0x00])
+        ));
+    }
+}
@@ -12,6 +12,18 @@ mod akm0110;
 /// Type to represent a keyboard scancode
 pub type Scancode = u8;
 
+/// Gets the 'Snow universal' scancode of a modifier key by name
+/// (shift, option, command, control)
+pub fn modifier_scancode(name: &str) -> Option<Scancode> {
+    match name.to_ascii_lowercase().as_str() {
+        "shift" => Some(0x38),
+        "option" | "alt" => Some(0x3A),
+        "command" | "cmd" => Some(0x37),
+        "control" | "ctrl" => Some(0x36),
+        _ => None,
+    }
+}
+
 /// A keyboard event. Inner value is the scancode
 pub enum KeyEvent {
     KeyDown(u8),
@@ -12,6 +12,12 @@ pub struct AudioState {
     pub receiver: Receiver<AudioBuffer>,
     buffer: Vec<u8>,
     silent: bool,
+
+    /// Output volume (0.0 - 1.0)
+    volume: f32,
+
+    /// Output muted
+    muted: bool,
 }
 
 impl Default for AudioState {
@@ -22,6 +28,8 @@ impl Default for AudioState {
             receiver,
             buffer: Vec::with_capacity(AUDIO_BUFFER_SIZE),
             silent: true,
+            volume: 1.0,
+            muted: false,
         }
     }
 }
@@ -36,7 +44,7 @@ impl AudioState {
         if self.buffer.len() >= AUDIO_BUFFER_SIZE {
             let buffer = std::mem::replace(&mut self.buffer, Vec::with_capacity(AUDIO_BUFFER_SIZE));
             self.silent = buffer.iter().all(|&s| s == buffer[0]);
-            self.sender.send(buffer.into_boxed_slice())?;
+            self.sender.send(self.scale(buffer).into_boxed_slice())?;
         }
         Ok(())
     }
@@ -44,4 +52,64 @@ impl AudioState {
     pub fn is_silent(&self) -> bool {
         self.silent
     }
+
+    /// Applies volume/mute to a buffer of samples. Samples are unsigned, centered
+    /// around 0x80.
+    fn scale(&self, mut buffer: Vec<u8>) -> Vec<u8> {
+        if self.muted {
+            buffer.fill(0x80);
+        } else if self.volume < 1.0 {
+            for s in &mut buffer {
+                *s = ((f32::from(*s) - 128.0) * self.volume + 128.0) as u8;
+            }
+        }
+        buffer
+    }
+
+    /// Sets the output volume (0.0 - 1.0). Out of range values are clamped.
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = if volume.is_nan() {
+            1.0
+        } else {
+            volume.clamp(0.0, 1.0)
+        };
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volume_clamp() {
+        let mut audio = AudioState::default();
+        audio.set_volume(2.0);
+        assert_eq!(audio.get_volume(), 1.0);
+        audio.set_volume(-1.0);
+        assert_eq!(audio.get_volume(), 0.0);
+        audio.set_volume(0.5);
+        assert_eq!(audio.get_volume(), 0.5);
+    }
+
+    #[test]
+    fn volume_scale() {
+        let mut audio = AudioState::default();
+        assert_eq!(audio.scale(vec![0x00, 0x80, 0xFF]), vec![0x00, 0x80, 0xFF]);
+        audio.set_volume(0.5);
+        assert_eq!(audio.scale(vec![0x00, 0x80, 0xFF]), vec![0x40, 0x80, 0xBF]);
+        audio.set_muted(true);
+        assert_eq!(audio.scale(vec![0x00, 0x80, 0xFF]), vec![0x80, 0x80, 0x80]);
+    }
 }
@@ -3,16 +3,17 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use super::audio::{AudioReceiver, AudioState};
+use super::drivesound::DriveSounds;
 use super::scc::Scc;
 use super::scsi::ScsiController;
 use super::via::Via;
 use super::MacModel;
 use crate::bus::{Address, Bus, BusMember, BusResult, InspectableBus, IrqSource};
-use crate::emulator::comm::EmulatorSpeed;
+use crate::emulator::comm::{EmulatorSpeed, Watchpoint};
 use crate::mac::swim::Swim;
-use crate::mac::video::Video;
+use crate::mac::video::{Video, SCREEN_HEIGHT, SCREEN_WIDTH};
 use crate::renderer::Renderer;
-use crate::tickable::{Tickable, Ticks};
+use crate::tickable::{Tickable, Ticks, TICKS_PER_SECOND};
 use crate::types::{Byte, LatchingEvent};
 
 use anyhow::Result;
@@ -34,8 +35,16 @@ pub struct MacBus<TRenderer: Renderer> {
     scc: Scc,
     pub(crate) video: Video<TRenderer>,
     pub(crate) audio: AudioState,
+    pub(crate) drive_sounds: DriveSounds,
     eclock: Ticks,
     mouse_ready: bool,
+
+    /// Scaling factor applied to relative mouse movement
+    mouse_scale: f32,
+
+    /// Sub-pixel remainder of scaled relative mouse movement
+    mouse_residual: (f32, f32),
+
     pub(crate) swim: Swim,
     pub(crate) scsi: ScsiController,
 
@@ -58,6 +67,9 @@ pub struct MacBus<TRenderer: Renderer> {
 
     pub dbg_break: LatchingEvent,
 
+    /// Active memory access watchpoints
+    pub(crate) watchpoints: Vec<Watchpoint>,
+
     overlay: bool,
 
     /// Emulation speed setting
@@ -71,6 +83,9 @@ pub struct MacBus<TRenderer: Renderer> {
 
     /// VPA/E-clock sync in progress
     vpa_sync: bool,
+
+    /// Remaining time the programmer's interrupt switch is held down
+    progkey_pressed: Ticks,
 }
 
 impl<TRenderer> MacBus<TRenderer>
@@ -94,6 +109,18 @@ where
     const ADDR_RAWMOUSE_X: Address = 0x082E;
     /// CrsrNew address
     const ADDR_CRSRNEW: Address = 0x08CE;
+    /// Ticks address, vertical blanking interrupts since boot (32 bit)
+    const ADDR_TICKS: Address = 0x016A;
+
+    /// Duration of one frame at actual speed (60.14 Hz), in microseconds
+    const FRAMETIME_ACCURATE: f32 = 1_000_000.0 / 60.14;
+
+    /// Limits of the factor for scaled speed
+    const SPEED_FACTOR_MIN: f32 = 0.1;
+    const SPEED_FACTOR_MAX: f32 = 10.0;
+
+    /// Time the interrupt switch is held down for when pressed (100ms)
+    const PROGKEY_TIME: Ticks = TICKS_PER_SECOND / 10;
 
     pub fn new(model: MacModel, rom: &[u8], renderer: TRenderer) -> Self {
         let ram_size = model.ram_size();
@@ -112,11 +139,14 @@ where
             via: Via::new(model),
             video: Video::new(renderer),
             audio: AudioState::default(),
+            drive_sounds: DriveSounds::default(),
             eclock: 0,
             scc: Scc::new(),
             swim: Swim::new(model.fdd_drives(), model.fdd_hd()),
             scsi: ScsiController::new(),
             mouse_ready: false,
+            mouse_scale: 1.0,
+            mouse_residual: (0.0, 0.0),
 
             ram_mask: (ram_size - 1),
             rom_mask: rom.len() - 1,
@@ -129,11 +159,13 @@ where
             soundbuf_alt: sound_alt_start..(sound_alt_start + Self::SOUNDBUF_SIZE),
 
             dbg_break: LatchingEvent::default(),
+            watchpoints: vec![],
             overlay: true,
             speed: EmulatorSpeed::Accurate,
             last_audiosample: 0,
             vblank_time: Instant::now(),
             vpa_sync: false,
+            progkey_pressed: 0,
         };
 
         // Disable memory test
@@ -211,11 +243,11 @@ where
         // (writes also go through RAM)
         if self.fb_main.contains(&addr) {
             let offset = (addr - self.fb_main.start) as usize;
-            self.video.framebuffers[0][offset] = val;
+            self.video.write_framebuffer(0, offset, val);
         }
         if self.fb_alt.contains(&addr) {
             let offset = (addr - self.fb_alt.start) as usize;
-            self.video.framebuffers[1][offset] = val;
+            self.video.write_framebuffer(1, offset, val);
         }
 
         match addr {
@@ -311,6 +343,51 @@ where
         result
     }
 
+    /// Undoes the patch that skips the memory test, so the ROM runs its own memory
+    /// test at boot. Only has effect before the emulated machine is started.
+    pub fn enable_memtest(&mut self) {
+        if let Some((addr, _)) = self.model.disable_memtest() {
+            info!("Memory test enabled");
+            self.write_ram(addr, 0u32);
+        }
+    }
+
+    /// Reads the Ticks low-memory global, which the OS increments on every
+    /// vertical blanking interrupt
+    pub fn get_ticks(&self) -> u32 {
+        self.read_ram(Self::ADDR_TICKS)
+    }
+
+    /// Presses the programmer's interrupt switch
+    pub fn progkey(&mut self) {
+        info!("Interrupt switch pressed");
+        self.progkey_pressed = Self::PROGKEY_TIME;
+    }
+
+    /// Sets the scaling factor for relative mouse movement
+    pub fn set_mouse_scale(&mut self, scale: f32) {
+        self.mouse_scale = if scale.is_finite() && scale > 0.0 {
+            scale
+        } else {
+            1.0
+        };
+        self.mouse_residual = (0.0, 0.0);
+        info!("Mouse scale: {:.2}", self.mouse_scale);
+    }
+
+    /// Applies the mouse scale to relative movement, carrying over the fractional part
+    /// so slow movements at small scales are not lost.
+    fn mouse_scale_rel(&mut self, relx: i16, rely: i16) -> (i16, i16) {
+        if self.mouse_scale == 1.0 {
+            return (relx, rely);
+        }
+
+        let x = f32::from(relx) * self.mouse_scale + self.mouse_residual.0;
+        let y = f32::from(rely) * self.mouse_scale + self.mouse_residual.1;
+        self.mouse_residual = (x.fract(), y.fract());
+        (x.trunc() as i16, y.trunc() as i16)
+    }
+
     /// Updates the mouse position (relative coordinates) and button state
     pub fn mouse_update_rel(&mut self, relx: i16, rely: i16, button: Option<bool>) {
         let old_x = self.read_ram::<u16>(Self::ADDR_RAWMOUSE_X);
@@ -323,6 +400,7 @@ where
         }
         self.mouse_ready = true;
 
+        let (relx, rely) = self.mouse_scale_rel(relx, rely);
         if relx != 0 || rely != 0 {
             let new_x = old_x.wrapping_add_signed(relx);
             let new_y = old_y.wrapping_add_signed(rely);
@@ -364,6 +442,10 @@ where
         }
         self.mouse_ready = true;
 
+        // Keep the cursor within the visible screen area
+        let x = x.min(SCREEN_WIDTH as u16 - 1);
+        let y = y.min(SCREEN_HEIGHT as u16 - 1);
+
         // Report updated mouse coordinates to OS
         self.write_ram(Self::ADDR_MTEMP_X, x);
         self.write_ram(Self::ADDR_MTEMP_Y, y);
@@ -379,8 +461,31 @@ where
         self.write_ram(Self::ADDR_CRSRNEW, 1_u8);
     }
 
+    /// Tests a bus access against the watchpoints and breaks on a match
+    fn check_watchpoints(&mut self, addr: Address, write: bool) {
+        if self
+            .watchpoints
+            .iter()
+            .any(|w| w.addr == addr && if write { w.on_write } else { w.on_read })
+        {
+            info!(
+                "Watchpoint hit: {} ${:06X}",
+                if write { "write" } else { "read" },
+                addr
+            );
+            self.dbg_break.set();
+        }
+    }
+
     /// Configures emulator speed
     pub fn set_speed(&mut self, speed: EmulatorSpeed) {
+        let speed = match speed {
+            EmulatorSpeed::Scaled(factor) if factor.is_nan() => EmulatorSpeed::Scaled(1.0),
+            EmulatorSpeed::Scaled(factor) => {
+                EmulatorSpeed::Scaled(factor.clamp(Self::SPEED_FACTOR_MIN, Self::SPEED_FACTOR_MAX))
+            }
+            s => s,
+        };
         info!("Emulation speed: {:?}", speed);
         self.speed = speed;
     }
@@ -426,6 +531,9 @@ where
         if self.in_waitstate(addr) {
             return BusResult::WaitState;
         }
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, false);
+        }
 
         let val = if self.overlay {
             self.read_overlay(addr)
@@ -445,6 +553,9 @@ where
         if self.in_waitstate(addr) {
             return BusResult::WaitState;
         }
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, true);
+        }
 
         let written = if self.overlay {
             self.write_overlay(addr, val)
@@ -467,7 +578,10 @@ where
     }
 
     fn reset(&mut self) -> Result<()> {
+        // The RTC is battery backed and keeps its time and PRAM
+        let rtc = std::mem::take(&mut self.via.rtc);
         self.via = Via::new(self.model);
+        self.via.rtc = rtc;
         self.scc = Scc::new();
         if self.model <= MacModel::Plus {
             self.overlay = true;
@@ -484,6 +598,7 @@ where
         // This is called from the CPU, at the CPU clock speed
         assert_eq!(ticks, 1);
         self.cycles += ticks;
+        self.progkey_pressed = self.progkey_pressed.saturating_sub(ticks);
 
         self.eclock += ticks;
         while self.eclock >= 10 {
@@ -508,15 +623,20 @@ where
         if self.video.get_clr_vblank() {
             self.via.ifr.set_vblank(true);
 
-            if self.speed == EmulatorSpeed::Video {
-                // Sync to 60 fps video
+            let desired_frametime = match self.speed {
+                EmulatorSpeed::Video => Some(1_000_000 / 60),
+                EmulatorSpeed::VideoFps(fps) => Some(1_000_000 / u64::from(fps.max(1))),
+                EmulatorSpeed::Scaled(factor) => Some((Self::FRAMETIME_ACCURATE / factor) as u64),
+                _ => None,
+            };
+            if let Some(desired_frametime) = desired_frametime {
+                // Sync to video
                 let frametime = self.vblank_time.elapsed().as_micros() as u64;
-                const DESIRED_FRAMETIME: u64 = 1_000_000 / 60;
 
                 self.vblank_time = Instant::now();
 
-                if frametime < DESIRED_FRAMETIME {
-                    thread::sleep(Duration::from_micros(DESIRED_FRAMETIME - frametime));
+                if frametime < desired_frametime {
+                    thread::sleep(Duration::from_micros(desired_frametime - frametime));
                 }
             }
         }
@@ -528,7 +648,11 @@ where
             let soundon = self.via.a_out.sound() > 0 && !self.via.b_out.sndenb();
             let soundbuf = self.soundbuf();
             let pwm = soundbuf[scanline * 2 + 1];
-            let audiosample = if soundon { soundbuf[scanline * 2] } else { 0 };
+            let mut audiosample = if soundon { soundbuf[scanline * 2] } else { 0 };
+            if self.drive_sounds.enabled {
+                audiosample = audiosample
+                    .saturating_add(self.drive_sounds.next_sample(self.swim.drive_activity()));
+            }
 
             self.swim.push_pwm(pwm)?;
 
@@ -541,7 +665,7 @@ where
                     }
                 }
                 EmulatorSpeed::Uncapped => (),
-                EmulatorSpeed::Video => (),
+                EmulatorSpeed::Video | EmulatorSpeed::VideoFps(_) | EmulatorSpeed::Scaled(_) => (),
             }
             self.last_audiosample = audiosample;
         }
@@ -557,6 +681,10 @@ where
     TRenderer: Renderer,
 {
     fn get_irq(&mut self) -> Option<u8> {
+        // Programmer's interrupt switch (/IPL2)
+        if self.progkey_pressed > 0 {
+            return Some(4);
+        }
         // VIA IRQs
         if self.via.ifr.0 & self.via.ier.0 != 0 {
             return Some(1);
@@ -596,3 +724,123 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::renderer::NullRenderer;
+
+    use super::*;
+
+    fn bus() -> MacBus<NullRenderer> {
+        let renderer = NullRenderer::new(SCREEN_WIDTH, SCREEN_HEIGHT).unwrap();
+        let mut bus = MacBus::new(MacModel::Early128K, &[0; 64 * 1024], renderer);
+        // Mouse position as initialized by the ROM during boot
+        bus.write_ram(MacBus::<NullRenderer>::ADDR_RAWMOUSE_X, 15_u16);
+        bus.write_ram(MacBus::<NullRenderer>::ADDR_RAWMOUSE_Y, 15_u16);
+        bus
+    }
+
+    #[test]
+    fn mouse_abs_in_range() {
+        let mut bus = bus();
+        bus.mouse_update_abs(100, 200);
+        assert_eq!(
+            bus.read_ram::<u16>(MacBus::<NullRenderer>::ADDR_MTEMP_X),
+            100
+        );
+        assert_eq!(
+            bus.read_ram::<u16>(MacBus::<NullRenderer>::ADDR_MTEMP_Y),
+            200
+        );
+    }
+
+    #[test]
+    fn mouse_abs_clamped() {
+        let mut bus = bus();
+        bus.mouse_update_abs(1152, 870);
+        assert_eq!(
+            bus.read_ram::<u16>(MacBus::<NullRenderer>::ADDR_MTEMP_X),
+            SCREEN_WIDTH as u16 - 1
+        );
+        assert_eq!(
+            bus.read_ram::<u16>(MacBus::<NullRenderer>::ADDR_MTEMP_Y),
+            SCREEN_HEIGHT as u16 - 1
+        );
+    }
+
+    #[test]
+    fn mouse_rel_scaled() {
+        let mut bus = bus();
+        bus.set_mouse_scale(2.0);
+        bus.mouse_update_rel(10, -3, None);
+        assert_eq!(
+            bus.read_ram::<u16>(MacBus::<NullRenderer>::ADDR_MTEMP_X),
+            35
+        );
+        assert_eq!(bus.read_ram::<u16>(MacBus::<NullRenderer>::ADDR_MTEMP_Y), 9);
+    }
+
+    #[test]
+    fn mouse_rel_scaled_residual() {
+        let mut bus = bus();
+        bus.set_mouse_scale(0.5);
+
+        // Half a pixel does not move the cursor yet..
+        bus.mouse_update_rel(1, 0, None);
+        assert_eq!(
+            bus.read_ram::<u16>(MacBus::<NullRenderer>::ADDR_RAWMOUSE_X),
+            15
+        );
+
+        // ..but is carried over to the next movement
+        bus.mouse_update_rel(1, 0, None);
+        assert_eq!(
+            bus.read_ram::<u16>(MacBus::<NullRenderer>::ADDR_MTEMP_X),
+            16
+        );
+    }
+
+    #[test]
+    fn watchpoint_read() {
+        let mut bus = bus();
+        bus.watchpoints.push(Watchpoint {
+            addr: 0x1000,
+            on_read: true,
+            on_write: false,
+        });
+
+        bus.write(0x1000, 0x12);
+        assert!(!bus.dbg_break.get_clear());
+        bus.read(0x1001);
+        assert!(!bus.dbg_break.get_clear());
+        bus.read(0x1000);
+        assert!(bus.dbg_break.get_clear());
+    }
+
+    #[test]
+    fn progkey_irq() {
+        let mut bus = bus();
+        bus.set_speed(EmulatorSpeed::Uncapped);
+        assert_eq!(bus.get_irq(), None);
+
+        bus.progkey();
+        assert_eq!(bus.get_irq(), Some(4));
+
+        // Released after the switch was held down
+        for _ in 0..MacBus::<NullRenderer>::PROGKEY_TIME {
+            bus.tick(1).unwrap();
+        }
+        assert_eq!(bus.get_irq(), None);
+    }
+
+    #[test]
+    fn memtest_patch() {
+        let renderer = NullRenderer::new(SCREEN_WIDTH, SCREEN_HEIGHT).unwrap();
+        let mut bus = MacBus::new(MacModel::Plus, &[0; 128 * 1024], renderer);
+        let (addr, value) = MacModel::Plus.disable_memtest().unwrap();
+        assert_eq!(bus.read_ram::<u32>(addr), value);
+
+        bus.enable_memtest();
+        assert_eq!(bus.read_ram::<u32>(addr), 0);
+    }
+}
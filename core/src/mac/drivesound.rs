@@ -0,0 +1,104 @@
+//! Synthesized floppy drive sound effects (motor hum, head seek clicks)
+
+/// Audio sample rate (one sample per scanline), in Hz
+const SAMPLE_RATE: usize = 22255;
+
+/// Length of a head seek click, in samples (5ms)
+const SEEK_LEN: usize = SAMPLE_RATE / 200;
+/// Initial amplitude of a head seek click
+const SEEK_AMPLITUDE: usize = 40;
+/// Half period of the seek click tone, in samples (~1.4KHz)
+const SEEK_HALF_PERIOD: usize = 8;
+
+/// Period of the motor hum, in samples (~120Hz)
+const MOTOR_PERIOD: usize = SAMPLE_RATE / 120;
+/// Amplitude of the motor hum
+const MOTOR_AMPLITUDE: u8 = 6;
+
+/// Mixes drive sounds into the audio output, based on the state of the drives
+#[derive(Default)]
+pub struct DriveSounds {
+    /// Drive sounds are played
+    pub enabled: bool,
+
+    /// Head positions at the last sample, to detect seeks
+    last_tracks: Vec<usize>,
+
+    /// Samples left of the current seek click
+    seek_left: usize,
+
+    /// Position within the motor hum period
+    motor_phase: usize,
+}
+
+impl DriveSounds {
+    /// Produces the next sample offset to mix into the audio output.
+    /// `drives` is the head position and running state of every drive.
+    pub fn next_sample(&mut self, drives: impl Iterator<Item = (usize, bool)>) -> u8 {
+        let mut motor = false;
+        let mut seeked = false;
+        let mut count = 0;
+
+        for (idx, (track, running)) in drives.enumerate() {
+            if let Some(last) = self.last_tracks.get_mut(idx) {
+                seeked |= *last != track;
+                *last = track;
+            } else {
+                self.last_tracks.push(track);
+            }
+            motor |= running;
+            count = idx + 1;
+        }
+        self.last_tracks.truncate(count);
+
+        if seeked {
+            self.seek_left = SEEK_LEN;
+        }
+
+        let mut sample = 0;
+        if motor {
+            self.motor_phase = (self.motor_phase + 1) % MOTOR_PERIOD;
+            if self.motor_phase < MOTOR_PERIOD / 2 {
+                sample += MOTOR_AMPLITUDE;
+            }
+        }
+        if self.seek_left > 0 {
+            // Square wave decaying over the length of the click
+            if (SEEK_LEN - self.seek_left) / SEEK_HALF_PERIOD % 2 == 0 {
+                sample += (SEEK_AMPLITUDE * self.seek_left / SEEK_LEN) as u8;
+            }
+            self.seek_left -= 1;
+        }
+        sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silent_when_idle() {
+        let mut ds = DriveSounds::default();
+        for _ in 0..1000 {
+            assert_eq!(ds.next_sample([(4, false), (0, false)].into_iter()), 0);
+        }
+    }
+
+    #[test]
+    fn seek_click() {
+        let mut ds = DriveSounds::default();
+        ds.next_sample([(4, false)].into_iter());
+        assert_eq!(
+            ds.next_sample([(5, false)].into_iter()),
+            SEEK_AMPLITUDE as u8
+        );
+
+        // Click fades out
+        let rest = (1..SEEK_LEN)
+            .map(|_| ds.next_sample([(5, false)].into_iter()))
+            .collect::<Vec<_>>();
+        assert!(rest.iter().any(|&s| s > 0));
+        assert_eq!(ds.next_sample([(5, false)].into_iter()), 0);
+    }
+}
@@ -10,6 +10,7 @@ use crate::{bus::Address, keymap::Keymap, tickable::Ticks};
 pub mod adb;
 pub mod audio;
 pub mod bus;
+pub mod drivesound;
 pub mod pluskbd;
 pub mod rtc;
 pub mod scc;
@@ -146,6 +147,73 @@ impl MacModel {
             None
         }
     }
+
+    /// Identifies a ROM from its header, also for ROMs that are not supported.
+    pub fn identify_rom(rom: &[u8]) -> RomInfo {
+        let checksum = rom
+            .get(0..4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+            .unwrap_or_default();
+        let version = rom
+            .get(8..10)
+            .map(|b| u16::from_be_bytes(b.try_into().unwrap()))
+            .unwrap_or_default();
+
+        RomInfo {
+            checksum,
+            version,
+            size: rom.len(),
+            model: Self::detect_from_rom(rom),
+        }
+    }
+}
+
+/// Information about a ROM image, as found in the ROM header
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    /// Checksum longword (offset 0)
+    pub checksum: u32,
+    /// ROM version word (offset 8)
+    pub version: u16,
+    /// Size of the ROM image, in bytes
+    pub size: usize,
+    /// Exact match to a supported model
+    pub model: Option<MacModel>,
+}
+
+impl RomInfo {
+    /// Best guess of the family of machines the ROM belongs to, based on the ROM version.
+    pub const fn family(&self) -> Option<&'static str> {
+        match self.version & 0xFF {
+            0x69 => Some("Macintosh 128K/512K"),
+            0x75 => Some("Macintosh Plus/512Ke"),
+            0x76 => Some("Macintosh SE/Classic"),
+            0x78 => Some("Macintosh II"),
+            0x7A => Some("Macintosh Portable"),
+            0x7C => Some("Macintosh II/LC"),
+            0x7D..=0x7F => Some("Quadra/PowerBook"),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RomInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(model) = self.model {
+            write!(f, "{}", model)?;
+        } else if let Some(family) = self.family() {
+            write!(f, "unknown {}-family ROM", family)?;
+        } else {
+            write!(f, "unknown ROM")?;
+        }
+        write!(
+            f,
+            " (version ${:04X}, checksum ${:08X}, {}KB)",
+            self.version,
+            self.checksum,
+            self.size / 1024
+        )
+    }
 }
 
 impl Display for MacModel {
@@ -183,6 +251,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn identify_unknown_rom() {
+        let mut rom = vec![0; 512 * 1024];
+        rom[0..4].copy_from_slice(&[0x36, 0x8C, 0xAD, 0xFE]);
+        rom[8..10].copy_from_slice(&[0x06, 0x7C]);
+
+        let info = MacModel::identify_rom(&rom);
+        assert_eq!(info.checksum, 0x368CADFE);
+        assert_eq!(info.version, 0x067C);
+        assert_eq!(info.model, None);
+        assert_eq!(info.family(), Some("Macintosh II/LC"));
+
+        // Too short for a header
+        let info = MacModel::identify_rom(&[0x12]);
+        assert_eq!(info.checksum, 0);
+        assert_eq!(info.family(), None);
+    }
+
     #[test]
     fn interleave_se_classic() {
         for m in &[MacModel::SE, MacModel::Classic] {
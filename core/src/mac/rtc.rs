@@ -2,7 +2,7 @@
 use std::fs::OpenOptions;
 
 use arrayvec::ArrayVec;
-use chrono::{Local, NaiveDate};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeDelta};
 
 #[cfg(feature = "mmap")]
 use fs2::FileExt;
@@ -12,6 +12,13 @@ use memmap2::MmapMut;
 
 const PRAM_SIZE: usize = 256;
 
+/// PRAM location of the extended PRAM validity signature
+const PRAM_XPRAM_SIGNATURE: usize = 0x0C;
+/// Extended PRAM validity signature, written by the ROM when it initializes PRAM
+const XPRAM_SIGNATURE: &[u8; 4] = b"NuMc";
+/// PRAM location of the default startup device (4 bytes)
+const PRAM_STARTUP_DEVICE: usize = 0x78;
+
 /// Macintosh Real-Time Clock
 pub struct Rtc {
     io_enable: bool,
@@ -111,15 +118,7 @@ impl RtcData {
 impl Default for Rtc {
     fn default() -> Self {
         // Initialize clock from host system
-        let seconds = Local::now()
-            .naive_local()
-            .signed_duration_since(
-                NaiveDate::from_ymd_opt(1904, 1, 1)
-                    .unwrap()
-                    .and_hms_opt(0, 0, 0)
-                    .unwrap(),
-            )
-            .num_seconds() as u32;
+        let seconds = Self::datetime_to_seconds(Local::now().naive_local());
 
         Self {
             io_enable: false,
@@ -139,6 +138,41 @@ impl Default for Rtc {
 }
 
 impl Rtc {
+    /// Start of the Macintosh epoch: 1904-01-01 00:00:00
+    fn epoch() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(1904, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    /// Converts a date/time to a value for the seconds counter
+    ///
+    /// The counter is 32-bit and rolls over on 2040-02-06 06:28:16, just like the
+    /// real RTC. Dates past that point (or before 1904) are wrapped modulo 2^32.
+    pub fn datetime_to_seconds(dt: NaiveDateTime) -> u32 {
+        dt.signed_duration_since(Self::epoch())
+            .num_seconds()
+            .rem_euclid(1 << 32) as u32
+    }
+
+    /// Converts a seconds counter value to a date/time
+    ///
+    /// Always yields a date between 1904-01-01 and 2040-02-06.
+    pub fn seconds_to_datetime(seconds: u32) -> NaiveDateTime {
+        Self::epoch() + TimeDelta::seconds(seconds.into())
+    }
+
+    /// Sets the clock to the specified date/time
+    pub fn set_datetime(&mut self, dt: NaiveDateTime) {
+        self.data.seconds = Self::datetime_to_seconds(dt);
+    }
+
+    /// Gets the current date/time of the clock
+    pub fn get_datetime(&self) -> NaiveDateTime {
+        Self::seconds_to_datetime(self.data.seconds)
+    }
+
     /// Loads a data file into PRAM
     pub fn load_pram(&mut self, filename: &str) {
         let Some(pram) = RtcData::load_pram(filename) else {
@@ -153,6 +187,32 @@ impl Rtc {
         self.data.pram = pram;
     }
 
+    /// Clears PRAM ('zapping PRAM'). The ROM detects the invalid PRAM contents and
+    /// initializes PRAM to defaults on the next boot. If PRAM is persisted, the file
+    /// is cleared as well.
+    pub fn reset_pram(&mut self) {
+        self.data.pram.fill(0);
+        info!("PRAM reset");
+    }
+
+    /// Sets the default startup device in PRAM, like the Startup Disk control panel.
+    /// `None` clears the setting, after which the ROM searches for a startup disk.
+    pub fn set_startup_scsi(&mut self, id: Option<usize>) {
+        let value = match id {
+            Some(id) => {
+                // Driver reference number of the SCSI driver for this ID
+                let [msb, lsb] = (-33 - id as i16).to_be_bytes();
+                [0, 0, msb, lsb]
+            }
+            None => [0; 4],
+        };
+
+        if self.data.pram[PRAM_XPRAM_SIGNATURE..(PRAM_XPRAM_SIGNATURE + 4)] != *XPRAM_SIGNATURE {
+            warn!("PRAM is not initialized yet, the ROM may reset the startup disk on boot");
+        }
+        self.data.pram[PRAM_STARTUP_DEVICE..(PRAM_STARTUP_DEVICE + 4)].copy_from_slice(&value);
+    }
+
     /// Pokes the RTC that one second has passed
     /// In the emulator, one second interrupt is driven by the VIA for ease.
     pub fn second(&mut self) {
@@ -309,3 +369,69 @@ impl Rtc {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dt(y: i32, m: u32, d: u32, hh: u32, mm: u32, ss: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(hh, mm, ss)
+            .unwrap()
+    }
+
+    #[test]
+    fn reset_pram() {
+        let mut rtc = Rtc::default();
+        rtc.data.pram[0x13] = 0xA8;
+        rtc.reset_pram();
+        assert!(rtc.data.pram.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn startup_scsi() {
+        let mut rtc = Rtc::default();
+        rtc.set_startup_scsi(Some(0));
+        assert_eq!(rtc.data.pram[0x78..0x7C], [0x00, 0x00, 0xFF, 0xDF]);
+        rtc.set_startup_scsi(Some(6));
+        assert_eq!(rtc.data.pram[0x78..0x7C], [0x00, 0x00, 0xFF, 0xD9]);
+        rtc.set_startup_scsi(None);
+        assert_eq!(rtc.data.pram[0x78..0x7C], [0x00; 4]);
+    }
+
+    #[test]
+    fn epoch() {
+        assert_eq!(Rtc::datetime_to_seconds(dt(1904, 1, 1, 0, 0, 0)), 0);
+        assert_eq!(Rtc::seconds_to_datetime(0), dt(1904, 1, 1, 0, 0, 0));
+    }
+
+    #[test]
+    fn date_2039() {
+        let d = dt(2039, 12, 31, 23, 59, 59);
+        assert_eq!(Rtc::datetime_to_seconds(d), 4291833599);
+        assert_eq!(Rtc::seconds_to_datetime(4291833599), d);
+    }
+
+    #[test]
+    fn rollover_2040() {
+        assert_eq!(
+            Rtc::datetime_to_seconds(dt(2040, 2, 6, 6, 28, 15)),
+            u32::MAX
+        );
+        assert_eq!(
+            Rtc::seconds_to_datetime(u32::MAX),
+            dt(2040, 2, 6, 6, 28, 15)
+        );
+        assert_eq!(Rtc::datetime_to_seconds(dt(2040, 2, 6, 6, 28, 16)), 0);
+        assert_eq!(Rtc::datetime_to_seconds(dt(2040, 2, 6, 6, 28, 17)), 1);
+    }
+
+    #[test]
+    fn rollover_tick() {
+        let mut rtc = Rtc::default();
+        rtc.set_datetime(dt(2040, 2, 6, 6, 28, 15));
+        rtc.second();
+        assert_eq!(rtc.get_datetime(), dt(1904, 1, 1, 0, 0, 0));
+    }
+}
@@ -40,7 +40,7 @@
 //!     REQ_ACK_Message --> End: Command complete
 //! ```
 
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::path::Path;
 
 use anyhow::{bail, Result};
@@ -58,7 +58,24 @@ use crate::bus::{Address, BusMember};
 pub const STATUS_GOOD: u8 = 0;
 pub const STATUS_CHECK_CONDITION: u8 = 2;
 
+/// Sense key: no error
+pub const SENSE_NO_SENSE: u8 = 0x00;
+/// Sense key: invalid command or parameters
+pub const SENSE_ILLEGAL_REQUEST: u8 = 0x05;
+/// Sense key: write attempted to a write protected medium
+pub const SENSE_DATA_PROTECT: u8 = 0x07;
+/// Additional sense code: invalid command operation code
+const ASC_INVALID_OPCODE: u8 = 0x20;
+/// Additional sense code: logical block address out of range
+const ASC_LBA_OUT_OF_RANGE: u8 = 0x21;
+/// Additional sense code: invalid field in command
+const ASC_INVALID_FIELD_IN_CDB: u8 = 0x24;
+/// Additional sense code: write protected
+const ASC_WRITE_PROTECTED: u8 = 0x27;
+
 pub const DISK_BLOCKSIZE: usize = 512;
+/// Largest disk image size the Mac SCSI Manager can address (2GB)
+pub const DISK_MAX_SIZE: usize = 2 * 1024 * 1024 * 1024;
 
 #[allow(dead_code)]
 #[derive(Debug, PartialEq, Eq)]
@@ -79,6 +96,7 @@ enum ScsiBusPhase {
 }
 
 /// Result of a command
+#[derive(Debug, PartialEq, Eq)]
 enum ScsiCmdResult {
     /// Immediately turn to the Status phase
     Status(u8),
@@ -219,6 +237,20 @@ pub struct ScsiController {
 
     #[cfg(not(feature = "mmap"))]
     disks: [Option<Vec<u8>>; Self::MAX_TARGETS],
+
+    /// Disks are write protected, writes are refused and never reach the image
+    writeprotect: [bool; Self::MAX_TARGETS],
+
+    /// Sense key and additional sense code of the last failed command,
+    /// reported by REQUEST SENSE
+    sense: (u8, u8),
+
+    /// Copy-on-write overlays, written blocks are kept here instead of in the disk image.
+    /// These only live in memory and are lost on exit unless committed.
+    overlays: [Option<BTreeMap<usize, Vec<u8>>>; Self::MAX_TARGETS],
+
+    /// Disks have writes that have not been flushed to the disk image yet
+    dirty: [bool; Self::MAX_TARGETS],
 }
 
 impl ScsiController {
@@ -233,6 +265,174 @@ impl ScsiController {
         Some(self.disks[id].as_ref()?.len())
     }
 
+    /// Returns whether the disk on the given SCSI ID is write protected.
+    pub fn get_write_protect(&self, id: usize) -> bool {
+        self.writeprotect[id]
+    }
+
+    /// Write protects a disk. The disk image is not modified while it is write protected.
+    pub fn set_write_protect(&mut self, id: usize, wp: bool) -> Result<()> {
+        if id >= Self::MAX_TARGETS {
+            bail!("Invalid SCSI ID {}", id);
+        }
+        if self.disks[id].is_none() {
+            bail!("No disk on SCSI ID {}", id);
+        }
+        self.writeprotect[id] = wp;
+        Ok(())
+    }
+
+    /// Returns the amount of blocks in the copy-on-write overlay of a disk, or None if
+    /// the disk has no overlay.
+    pub fn get_overlay_blocks(&self, id: usize) -> Option<usize> {
+        Some(self.overlays[id].as_ref()?.len())
+    }
+
+    /// Starts a copy-on-write overlay on a disk. From here on, writes are kept in memory
+    /// and the disk image remains untouched until the overlay is committed.
+    pub fn overlay_start(&mut self, id: usize) -> Result<()> {
+        if id >= Self::MAX_TARGETS {
+            bail!("Invalid SCSI ID {}", id);
+        }
+        if self.disks[id].is_none() {
+            bail!("No disk on SCSI ID {}", id);
+        }
+        if self.overlays[id].is_some() {
+            bail!("SCSI ID {} already has an overlay", id);
+        }
+        self.overlays[id] = Some(BTreeMap::new());
+        Ok(())
+    }
+
+    /// Writes the blocks in the overlay of a disk to the disk image and removes the
+    /// overlay. Returns the amount of blocks written.
+    pub fn overlay_commit(&mut self, id: usize) -> Result<usize> {
+        if id >= Self::MAX_TARGETS {
+            bail!("Invalid SCSI ID {}", id);
+        }
+        if self.overlays[id].is_none() {
+            bail!("SCSI ID {} has no overlay", id);
+        }
+        if self.writeprotect[id] {
+            bail!("SCSI ID {} is write protected", id);
+        }
+        let overlay = self.overlays[id].take().unwrap();
+        let disk = self.disks[id].as_mut().unwrap();
+        for (&block, data) in &overlay {
+            disk[(block * DISK_BLOCKSIZE)..((block + 1) * DISK_BLOCKSIZE)].copy_from_slice(data);
+        }
+        self.dirty[id] |= !overlay.is_empty();
+        Ok(overlay.len())
+    }
+
+    /// Throws away the overlay of a disk, undoing all writes since the overlay was
+    /// started. Returns the amount of blocks discarded.
+    pub fn overlay_discard(&mut self, id: usize) -> Result<usize> {
+        if id >= Self::MAX_TARGETS {
+            bail!("Invalid SCSI ID {}", id);
+        }
+        let Some(overlay) = self.overlays[id].take() else {
+            bail!("SCSI ID {} has no overlay", id);
+        };
+        Ok(overlay.len())
+    }
+
+    /// Reads blocks from a disk, through the overlay if present.
+    /// Returns None if reading beyond the end of the disk.
+    fn read_blocks(&self, id: usize, block: usize, count: usize) -> Option<Vec<u8>> {
+        let disk = self.disks[id].as_ref()?;
+        if (block + count) * DISK_BLOCKSIZE > disk.len() {
+            return None;
+        }
+
+        let mut data = disk[(block * DISK_BLOCKSIZE)..((block + count) * DISK_BLOCKSIZE)].to_vec();
+        if let Some(overlay) = &self.overlays[id] {
+            for (&b, bdata) in overlay.range(block..(block + count)) {
+                let offset = (b - block) * DISK_BLOCKSIZE;
+                data[offset..(offset + DISK_BLOCKSIZE)].copy_from_slice(bdata);
+            }
+        }
+        Some(data)
+    }
+
+    /// Writes blocks to a disk, or to the overlay if present.
+    /// Returns false if writing beyond the end of the disk.
+    fn write_blocks(&mut self, id: usize, block: usize, data: &[u8]) -> bool {
+        let Some(disk) = self.disks[id].as_mut() else {
+            return false;
+        };
+        if block * DISK_BLOCKSIZE + data.len() > disk.len() {
+            return false;
+        }
+
+        if let Some(overlay) = &mut self.overlays[id] {
+            for (i, bdata) in data.chunks_exact(DISK_BLOCKSIZE).enumerate() {
+                overlay.insert(block + i, bdata.to_vec());
+            }
+        } else {
+            disk[(block * DISK_BLOCKSIZE)..(block * DISK_BLOCKSIZE + data.len())]
+                .copy_from_slice(data);
+            self.dirty[id] = true;
+        }
+        true
+    }
+
+    /// Returns whether a disk has writes that have not been flushed to the disk image.
+    pub fn is_dirty(&self, id: usize) -> bool {
+        self.dirty[id]
+    }
+
+    /// Writes all pending changes of all disks to the disk images.
+    pub fn flush(&mut self) -> Result<()> {
+        for id in 0..Self::MAX_TARGETS {
+            if !self.dirty[id] {
+                continue;
+            }
+            let Some(disk) = self.disks[id].as_ref() else {
+                continue;
+            };
+
+            #[cfg(feature = "mmap")]
+            disk.flush()?;
+
+            #[cfg(not(feature = "mmap"))]
+            std::fs::write(Self::disk_filename(id), disk)?;
+
+            self.dirty[id] = false;
+        }
+        Ok(())
+    }
+
+    /// Filename of the disk image for a SCSI ID
+    fn disk_filename(id: usize) -> String {
+        format!("hdd{}.img", id)
+    }
+
+    /// Checks whether a disk image of the given size can be used, logs the
+    /// reason if not.
+    fn check_disk_size(filename: &str, size: usize) -> bool {
+        if size == 0 {
+            error!("Cannot load disk image {}: image is empty", filename);
+            false
+        } else if size % DISK_BLOCKSIZE != 0 {
+            error!(
+                "Cannot load disk image {}: not multiple of {}",
+                filename, DISK_BLOCKSIZE
+            );
+            false
+        } else if size > DISK_MAX_SIZE {
+            error!(
+                "Cannot load disk image {}: image is {} MB, the maximum size the Macintosh can address is {} MB",
+                filename,
+                size / 1024 / 1024,
+                DISK_MAX_SIZE / 1024 / 1024
+            );
+            false
+        } else {
+            true
+        }
+    }
+
     /// Try to load a disk image, given the filename of the image.
     ///
     /// This locks the file on disk and memory maps the file for use by
@@ -265,11 +465,7 @@ impl ScsiController {
                 .ok()?
         };
 
-        if mmapped.len() % DISK_BLOCKSIZE != 0 {
-            error!(
-                "Cannot load disk image {}: not multiple of {}",
-                filename, DISK_BLOCKSIZE
-            );
+        if !Self::check_disk_size(filename, mmapped.len()) {
             return None;
         }
 
@@ -293,11 +489,7 @@ impl ScsiController {
             }
         };
 
-        if disk.len() % DISK_BLOCKSIZE != 0 {
-            error!(
-                "Cannot load disk image {}: not multiple of {}",
-                filename, DISK_BLOCKSIZE
-            );
+        if !Self::check_disk_size(filename, disk.len()) {
             return None;
         }
 
@@ -322,7 +514,7 @@ impl ScsiController {
             dataout_len: 0,
             status: 0,
             disks: core::array::from_fn(|n| {
-                let filename = format!("hdd{}.img", n);
+                let filename = Self::disk_filename(n);
                 let r = Self::load_disk(&filename);
                 if r.is_some() {
                     info!("SCSI ID {}: Enabled: loaded {}", n, filename);
@@ -331,6 +523,10 @@ impl ScsiController {
                 }
                 r
             }),
+            writeprotect: [false; Self::MAX_TARGETS],
+            sense: (SENSE_NO_SENSE, 0),
+            overlays: core::array::from_fn(|_| None),
+            dirty: [false; Self::MAX_TARGETS],
         }
     }
 
@@ -428,6 +624,10 @@ impl ScsiController {
                 | 0x15
                 // MODE SENSE(6)
                 | 0x1A
+                // START STOP UNIT
+                | 0x1B
+                // PREVENT ALLOW MEDIUM REMOVAL
+                | 0x1E
                 => 6,
                 // READ CAPACITY(10)
                 0x25
@@ -441,12 +641,25 @@ impl ScsiController {
                 | 0x3C
                 => 10,
             _ => {
-                warn!("cmd_get_len unknown command: {:02X}", cmdnum);
-                6
+                // Unknown command, the length follows from the group code so the
+                // command can still be received and rejected.
+                let len = match cmdnum >> 5 {
+                    1 | 2 => 10,
+                    5 => 12,
+                    _ => 6,
+                };
+                warn!("cmd_get_len unknown command: {:02X}, assuming {} bytes", cmdnum, len);
+                len
             }
         }
     }
 
+    /// Stores the sense data for the next REQUEST SENSE and returns CHECK CONDITION
+    fn check_condition(&mut self, key: u8, asc: u8) -> ScsiCmdResult {
+        self.sense = (key, asc);
+        ScsiCmdResult::Status(STATUS_CHECK_CONDITION)
+    }
+
     fn cmd_run(&mut self, outdata: Option<&[u8]>) -> Result<ScsiCmdResult> {
         let cmd = &self.cmdbuf;
 
@@ -457,8 +670,17 @@ impl ScsiController {
             }
             0x03 => {
                 // REQUEST SENSE
-                let result = vec![0; 13];
-                // 0 = no error
+                let mut result = vec![0; 13];
+                let (key, asc) = self.sense;
+                if key != SENSE_NO_SENSE {
+                    // Current error, fixed format
+                    result[0] = 0x70;
+                    result[2] = key;
+                    // Additional sense length
+                    result[7] = result.len() as u8 - 8;
+                    result[12] = asc;
+                    self.sense = (SENSE_NO_SENSE, 0);
+                }
                 Ok(ScsiCmdResult::DataIn(result))
             }
             0x04 => {
@@ -467,34 +689,34 @@ impl ScsiController {
             }
             0x08 => {
                 // READ(6)
-                let disk = self.disks[self.sel_id].as_ref().unwrap();
                 let blocknum = (u32::from_be_bytes(cmd[0..4].try_into()?) & 0x1F_FFFF) as usize;
                 let blockcnt = if cmd[4] == 0 { 256 } else { cmd[4] as usize };
 
-                if (blocknum + blockcnt) * DISK_BLOCKSIZE > disk.len() {
-                    error!("Reading beyond disk");
-                    Ok(ScsiCmdResult::Status(STATUS_CHECK_CONDITION))
+                if let Some(data) = self.read_blocks(self.sel_id, blocknum, blockcnt) {
+                    Ok(ScsiCmdResult::DataIn(data))
                 } else {
-                    Ok(ScsiCmdResult::DataIn(
-                        disk[(blocknum * DISK_BLOCKSIZE)..((blocknum + blockcnt) * DISK_BLOCKSIZE)]
-                            .to_vec(),
-                    ))
+                    error!("Reading beyond disk");
+                    Ok(self.check_condition(SENSE_ILLEGAL_REQUEST, ASC_LBA_OUT_OF_RANGE))
                 }
             }
             0x0A => {
                 // WRITE(6)
+                if self.writeprotect[self.sel_id] {
+                    warn!(
+                        "SCSI ID {}: write to write protected disk refused",
+                        self.sel_id
+                    );
+                    return Ok(self.check_condition(SENSE_DATA_PROTECT, ASC_WRITE_PROTECTED));
+                }
                 let blocknum = (u32::from_be_bytes(cmd[0..4].try_into()?) & 0x1F_FFFF) as usize;
                 let blockcnt = if cmd[4] == 0 { 256 } else { cmd[4] as usize };
 
                 if let Some(data) = outdata {
-                    let disk = self.disks[self.sel_id].as_mut().unwrap();
-                    if (blocknum + blockcnt) * DISK_BLOCKSIZE > disk.len() {
-                        error!("Writing beyond disk");
-                        Ok(ScsiCmdResult::Status(STATUS_CHECK_CONDITION))
-                    } else {
-                        disk[(blocknum * DISK_BLOCKSIZE)..((blocknum + blockcnt) * DISK_BLOCKSIZE)]
-                            .copy_from_slice(data);
+                    if self.write_blocks(self.sel_id, blocknum, data) {
                         Ok(ScsiCmdResult::Status(STATUS_GOOD))
+                    } else {
+                        error!("Writing beyond disk");
+                        Ok(self.check_condition(SENSE_ILLEGAL_REQUEST, ASC_LBA_OUT_OF_RANGE))
                     }
                 } else {
                     Ok(ScsiCmdResult::DataOut(blockcnt * DISK_BLOCKSIZE))
@@ -523,7 +745,7 @@ impl ScsiController {
             }
             0x1A => {
                 // MODE SENSE(6)
-                match cmd[2] & 0x3F {
+                let page = match cmd[2] & 0x3F {
                     0x01 => {
                         // Read/write recovery page
                         let mut result = vec![0; 12];
@@ -535,7 +757,7 @@ impl ScsiController {
                         // Error recovery stuff, can remain at 0.
                         // Also, HD SC Setup doesn't seem to care as long as we respond to this command.
 
-                        Ok(ScsiCmdResult::DataIn(result))
+                        result
                     }
                     0x03 => {
                         // Format device page
@@ -550,7 +772,7 @@ impl ScsiController {
                         // sectors/tracks are reserved for defect management.
                         // Also, HD SC Setup doesn't seem to care as long as we respond to this command.
 
-                        Ok(ScsiCmdResult::DataIn(result))
+                        result
                     }
                     0x30 => {
                         // ? Non-standard mode page
@@ -565,14 +787,52 @@ impl ScsiController {
                         // https://68kmla.org/bb/index.php?threads/apple-rom-hard-disks.44920/post-493863
                         result[14..(14 + 20)].copy_from_slice(b"APPLE COMPUTER, INC.");
 
-                        Ok(ScsiCmdResult::DataIn(result))
+                        result
                     }
                     _ => {
                         warn!("Unknown MODE SENSE page {:02X}", cmd[2]);
-                        Ok(ScsiCmdResult::Status(STATUS_CHECK_CONDITION))
+                        return Ok(
+                            self.check_condition(SENSE_ILLEGAL_REQUEST, ASC_INVALID_FIELD_IN_CDB)
+                        );
                     }
+                };
+
+                // Mode parameter header
+                let mut result = vec![
+                    // Mode data length
+                    (page.len() + 3) as u8,
+                    // Medium type
+                    0,
+                    // Device-specific parameter, bit 7 is write protect
+                    if self.writeprotect[self.sel_id] {
+                        0x80
+                    } else {
+                        0
+                    },
+                    // Block descriptor length
+                    0,
+                ];
+                result.extend(page);
+                Ok(ScsiCmdResult::DataIn(result))
+            }
+            0x1B => {
+                // START STOP UNIT
+                if cmd[4] & 0b10 != 0 {
+                    // Load/eject, the disk is not removable
+                    warn!(
+                        "SCSI ID {}: eject refused, medium not removable",
+                        self.sel_id
+                    );
+                    Ok(self.check_condition(SENSE_ILLEGAL_REQUEST, ASC_INVALID_FIELD_IN_CDB))
+                } else {
+                    Ok(ScsiCmdResult::Status(STATUS_GOOD))
                 }
             }
+            0x1E => {
+                // PREVENT ALLOW MEDIUM REMOVAL
+                // The disk is not removable, so there is nothing to prevent.
+                Ok(ScsiCmdResult::Status(STATUS_GOOD))
+            }
             0x25 => {
                 // READ CAPACITY(10)
                 let mut result = vec![0; 40];
@@ -586,34 +846,34 @@ impl ScsiController {
             }
             0x28 => {
                 // READ(10)
-                let disk = self.disks[self.sel_id].as_ref().unwrap();
                 let blocknum = (u32::from_be_bytes(cmd[2..6].try_into()?)) as usize;
                 let blockcnt = (u16::from_be_bytes(cmd[7..9].try_into()?)) as usize;
 
-                if (blocknum + blockcnt) * DISK_BLOCKSIZE > disk.len() {
-                    error!("Reading beyond disk");
-                    Ok(ScsiCmdResult::Status(STATUS_CHECK_CONDITION))
+                if let Some(data) = self.read_blocks(self.sel_id, blocknum, blockcnt) {
+                    Ok(ScsiCmdResult::DataIn(data))
                 } else {
-                    Ok(ScsiCmdResult::DataIn(
-                        disk[(blocknum * DISK_BLOCKSIZE)..((blocknum + blockcnt) * DISK_BLOCKSIZE)]
-                            .to_vec(),
-                    ))
+                    error!("Reading beyond disk");
+                    Ok(self.check_condition(SENSE_ILLEGAL_REQUEST, ASC_LBA_OUT_OF_RANGE))
                 }
             }
             0x2A => {
                 // WRITE(10)
+                if self.writeprotect[self.sel_id] {
+                    warn!(
+                        "SCSI ID {}: write to write protected disk refused",
+                        self.sel_id
+                    );
+                    return Ok(self.check_condition(SENSE_DATA_PROTECT, ASC_WRITE_PROTECTED));
+                }
                 let blocknum = (u32::from_be_bytes(cmd[2..6].try_into()?)) as usize;
                 let blockcnt = (u16::from_be_bytes(cmd[7..9].try_into()?)) as usize;
 
                 if let Some(data) = outdata {
-                    let disk = self.disks[self.sel_id].as_mut().unwrap();
-                    if (blocknum + blockcnt) * DISK_BLOCKSIZE > disk.len() {
-                        error!("Writing beyond disk");
-                        Ok(ScsiCmdResult::Status(STATUS_CHECK_CONDITION))
-                    } else {
-                        disk[(blocknum * DISK_BLOCKSIZE)..((blocknum + blockcnt) * DISK_BLOCKSIZE)]
-                            .copy_from_slice(data);
+                    if self.write_blocks(self.sel_id, blocknum, data) {
                         Ok(ScsiCmdResult::Status(STATUS_GOOD))
+                    } else {
+                        error!("Writing beyond disk");
+                        Ok(self.check_condition(SENSE_ILLEGAL_REQUEST, ASC_LBA_OUT_OF_RANGE))
                     }
                 } else {
                     Ok(ScsiCmdResult::DataOut(blockcnt * DISK_BLOCKSIZE))
@@ -631,8 +891,11 @@ impl ScsiController {
                 Ok(ScsiCmdResult::DataIn(result))
             }
             _ => {
-                error!("Unknown command {:02X}", cmd[0]);
-                Ok(ScsiCmdResult::Status(STATUS_CHECK_CONDITION))
+                error!(
+                    "SCSI ID {}: unknown command {:02X}, CDB: {:02X?}",
+                    self.sel_id, cmd[0], cmd
+                );
+                Ok(self.check_condition(SENSE_ILLEGAL_REQUEST, ASC_INVALID_OPCODE))
             }
         }
     }
@@ -876,3 +1139,192 @@ impl BusMember<Address> for ScsiController {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a controller with an empty in-memory disk on SCSI ID 0
+    fn scsi_with_disk(blocks: usize) -> ScsiController {
+        let mut scsi = ScsiController::new();
+        #[cfg(feature = "mmap")]
+        {
+            scsi.disks[0] = Some(MmapMut::map_anon(blocks * DISK_BLOCKSIZE).unwrap());
+        }
+        #[cfg(not(feature = "mmap"))]
+        {
+            scsi.disks[0] = Some(vec![0; blocks * DISK_BLOCKSIZE]);
+        }
+        scsi.sel_id = 0;
+        scsi
+    }
+
+    /// Runs REQUEST SENSE and returns the sense key and additional sense code
+    fn request_sense(scsi: &mut ScsiController) -> (u8, u8) {
+        scsi.cmdbuf = vec![0x03, 0, 0, 0, 13, 0];
+        let ScsiCmdResult::DataIn(sense) = scsi.cmd_run(None).unwrap() else {
+            panic!("No sense data");
+        };
+        (sense[2], sense[12])
+    }
+
+    #[test]
+    fn disk_size_valid() {
+        assert!(ScsiController::check_disk_size("test", DISK_BLOCKSIZE));
+        assert!(ScsiController::check_disk_size("test", 20 * 1024 * 1024));
+        assert!(ScsiController::check_disk_size("test", DISK_MAX_SIZE));
+    }
+
+    #[test]
+    fn disk_size_empty() {
+        assert!(!ScsiController::check_disk_size("test", 0));
+    }
+
+    #[test]
+    fn disk_size_unaligned() {
+        assert!(!ScsiController::check_disk_size("test", DISK_BLOCKSIZE + 1));
+        assert!(!ScsiController::check_disk_size("test", DISK_BLOCKSIZE - 1));
+    }
+
+    #[test]
+    fn disk_size_too_large() {
+        assert!(!ScsiController::check_disk_size(
+            "test",
+            DISK_MAX_SIZE + DISK_BLOCKSIZE
+        ));
+    }
+
+    #[test]
+    fn cmd_len_unknown() {
+        let scsi = ScsiController::new();
+        // MODE SENSE(10), READ(12), RESERVE(6)
+        assert_eq!(scsi.cmd_get_len(0x5A), 10);
+        assert_eq!(scsi.cmd_get_len(0xA8), 12);
+        assert_eq!(scsi.cmd_get_len(0x16), 6);
+    }
+
+    #[test]
+    fn unknown_cmd_sense() {
+        let mut scsi = ScsiController::new();
+        scsi.cmdbuf = vec![0x5A, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(
+            scsi.cmd_run(None).unwrap(),
+            ScsiCmdResult::Status(STATUS_CHECK_CONDITION)
+        );
+
+        // REQUEST SENSE reports the error once
+        scsi.cmdbuf = vec![0x03, 0, 0, 0, 13, 0];
+        let ScsiCmdResult::DataIn(sense) = scsi.cmd_run(None).unwrap() else {
+            panic!("No sense data");
+        };
+        assert_eq!(sense[2], SENSE_ILLEGAL_REQUEST);
+        assert_eq!(sense[12], ASC_INVALID_OPCODE);
+        let ScsiCmdResult::DataIn(sense) = scsi.cmd_run(None).unwrap() else {
+            panic!("No sense data");
+        };
+        assert_eq!(sense[2], SENSE_NO_SENSE);
+    }
+
+    #[test]
+    fn mode_sense_write_protect() {
+        let mut scsi = scsi_with_disk(16);
+        for wp in [false, true] {
+            scsi.set_write_protect(0, wp).unwrap();
+            scsi.cmdbuf = vec![0x1A, 0, 0x01, 0, 0xFF, 0];
+            let ScsiCmdResult::DataIn(result) = scsi.cmd_run(None).unwrap() else {
+                panic!("No mode sense data");
+            };
+            assert_eq!(usize::from(result[0]), result.len() - 1);
+            assert_eq!(result[2] & 0x80 != 0, wp);
+            assert_eq!(result[3], 0);
+            // Page follows the header
+            assert_eq!(result[4], 0x01);
+        }
+    }
+
+    #[test]
+    fn write_protected() {
+        let mut scsi = scsi_with_disk(16);
+        scsi.set_write_protect(0, true).unwrap();
+
+        // WRITE(6) and WRITE(10) of one block
+        for cmd in [
+            vec![0x0A, 0, 0, 0, 1, 0],
+            vec![0x2A, 0, 0, 0, 0, 0, 0, 0, 1, 0],
+        ] {
+            scsi.cmdbuf = cmd;
+            assert_eq!(
+                scsi.cmd_run(None).unwrap(),
+                ScsiCmdResult::Status(STATUS_CHECK_CONDITION)
+            );
+            assert_eq!(
+                request_sense(&mut scsi),
+                (SENSE_DATA_PROTECT, ASC_WRITE_PROTECTED)
+            );
+        }
+
+        // Writes go through again without write protection
+        scsi.set_write_protect(0, false).unwrap();
+        scsi.cmdbuf = vec![0x0A, 0, 0, 0, 1, 0];
+        assert_eq!(
+            scsi.cmd_run(None).unwrap(),
+            ScsiCmdResult::DataOut(DISK_BLOCKSIZE)
+        );
+        assert_eq!(request_sense(&mut scsi), (SENSE_NO_SENSE, 0));
+    }
+
+    /// A block filled with a value
+    fn block(value: u8) -> Vec<u8> {
+        vec![value; DISK_BLOCKSIZE]
+    }
+
+    #[test]
+    fn overlay_commit() {
+        let mut scsi = scsi_with_disk(16);
+        assert!(scsi.write_blocks(0, 2, &block(0x11)));
+        scsi.dirty[0] = false;
+        scsi.overlay_start(0).unwrap();
+
+        // Writes stay in the overlay, the disk image is unchanged
+        assert!(scsi.write_blocks(0, 3, &[block(0x22), block(0x33)].concat()));
+        assert_eq!(scsi.get_overlay_blocks(0), Some(2));
+        assert!(!scsi.is_dirty(0));
+        assert!(
+            scsi.disks[0].as_ref().unwrap()[(3 * DISK_BLOCKSIZE)..(5 * DISK_BLOCKSIZE)]
+                .iter()
+                .all(|&b| b == 0)
+        );
+
+        // Blocks not in the overlay are read from the disk image
+        assert_eq!(
+            scsi.read_blocks(0, 1, 4).unwrap(),
+            [block(0), block(0x11), block(0x22), block(0x33)].concat()
+        );
+
+        // Commit copies the overlay to the disk image
+        assert_eq!(scsi.overlay_commit(0).unwrap(), 2);
+        assert_eq!(scsi.get_overlay_blocks(0), None);
+        assert!(scsi.is_dirty(0));
+        assert_eq!(
+            &scsi.disks[0].as_ref().unwrap()[(2 * DISK_BLOCKSIZE)..(5 * DISK_BLOCKSIZE)],
+            [block(0x11), block(0x22), block(0x33)].concat()
+        );
+    }
+
+    #[test]
+    fn overlay_discard() {
+        let mut scsi = scsi_with_disk(16);
+        scsi.overlay_start(0).unwrap();
+        assert!(scsi.overlay_start(0).is_err());
+
+        assert!(scsi.write_blocks(0, 1, &block(0x44)));
+        assert_eq!(scsi.read_blocks(0, 1, 1).unwrap(), block(0x44));
+
+        // Discard restores the disk image contents
+        assert_eq!(scsi.overlay_discard(0).unwrap(), 1);
+        assert_eq!(scsi.get_overlay_blocks(0), None);
+        assert_eq!(scsi.read_blocks(0, 1, 1).unwrap(), block(0));
+        assert!(!scsi.is_dirty(0));
+        assert!(scsi.overlay_discard(0).is_err());
+    }
+}
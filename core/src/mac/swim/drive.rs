@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use log::*;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
@@ -67,6 +67,21 @@ impl DriveType {
         }
     }
 
+    /// Floppy types that can be used in this drive type
+    pub const fn compatible_floppies(self) -> &'static [FloppyType] {
+        match self {
+            Self::None => &[],
+            Self::GCR400K => &[FloppyType::Mac400K],
+            Self::GCR800K => &[FloppyType::Mac400K, FloppyType::Mac800K],
+            Self::SuperDrive => &[
+                FloppyType::Mac400K,
+                FloppyType::Mac800K,
+                FloppyType::Mfm720K,
+                FloppyType::Mfm144M,
+            ],
+        }
+    }
+
     pub const fn is_doublesided(self) -> bool {
         match self {
             Self::None => true,
@@ -191,6 +206,12 @@ pub(crate) struct FloppyDrive {
     pub(super) stepping: Ticks,
     pub(super) ejecting: Option<Ticks>,
 
+    /// Cycle count at which a swapped disk is inserted, after the previous disk was ejected
+    inserting: Option<Ticks>,
+
+    /// Disk was switched while the previous disk was inserted
+    switched: bool,
+
     /// Amount of flux ticks for current transition (for flux tracks)
     pub(super) flux_ticks: FluxTicks,
 
@@ -202,6 +223,9 @@ pub(crate) struct FloppyDrive {
     pub(super) pwm_avg_sum: i64,
     pub(super) pwm_avg_count: usize,
     pub(super) pwm_dutycycle: Ticks,
+
+    /// Spindle motor speed adjustment, in rounds/minute
+    rpm_adjustment: i32,
 }
 
 impl FloppyDrive {
@@ -211,6 +235,12 @@ impl FloppyDrive {
     /// Tacho pulses/disk revolution
     const TACHO_SPEED: Ticks = 60;
 
+    /// Maximum spindle motor speed adjustment (either direction), in rounds/minute
+    pub const RPM_ADJUSTMENT_MAX: i32 = 100;
+
+    /// Time the drive is empty when a disk is swapped, so the OS notices the eject
+    const SWAP_DELAY: Ticks = TICKS_PER_SECOND;
+
     pub fn new(idx: usize, drive_type: DriveType) -> Self {
         Self {
             idx,
@@ -226,6 +256,8 @@ impl FloppyDrive {
 
             stepping: 0,
             ejecting: None,
+            inserting: None,
+            switched: false,
 
             flux_ticks: 0,
             flux_ticks_left: 0,
@@ -235,6 +267,7 @@ impl FloppyDrive {
             pwm_avg_sum: 0,
             pwm_avg_count: 0,
             pwm_dutycycle: 0,
+            rpm_adjustment: 0,
         }
     }
 
@@ -280,11 +313,8 @@ impl FloppyDrive {
             }
             DriveReg::MFM => self.mfm,
             DriveReg::SUPERDRIVE => self.drive_type.io_superdrive(),
-            DriveReg::WRTPRT => {
-                // TODO write-protected until write is implemented
-                self.floppy.get_type() != FloppyType::Mfm144M && !self.floppy.get_write_protect()
-            }
-            DriveReg::SWITCHED => false,
+            DriveReg::WRTPRT => !self.floppy.get_write_protect(),
+            DriveReg::SWITCHED => self.switched,
             _ => {
                 warn!(
                     "Drive {}: unimplemented register read {:?} {:0b}",
@@ -371,19 +401,77 @@ impl FloppyDrive {
 
     /// Inserts a disk into the disk drive
     pub fn disk_insert(&mut self, image: FloppyImage) -> Result<()> {
+        if !self
+            .drive_type
+            .compatible_floppies()
+            .contains(&image.get_type())
+        {
+            bail!(
+                "{} disk is not compatible with {} drive",
+                image.get_type(),
+                self.drive_type
+            );
+        }
+
+        if self.floppy_inserted {
+            // Swapping disks, eject the current disk first so the OS sees the disk
+            // leaving and invalidates what it knows about it.
+            self.eject();
+            self.switched = true;
+            self.inserting = Some(self.cycles + Self::SWAP_DELAY);
+            self.floppy = image;
+            return Ok(());
+        }
+
+        self.floppy = image;
+        self.finish_insert();
+        Ok(())
+    }
+
+    /// Completes a delayed insertion of a swapped disk, once it is due
+    pub(super) fn update_insert(&mut self) {
+        if self.inserting.is_some_and(|t| t <= self.cycles) {
+            self.finish_insert();
+        }
+    }
+
+    fn finish_insert(&mut self) {
         info!(
             "Drive {}: disk inserted, {} tracks, title: '{}'",
             self.idx,
-            image.get_track_count() * image.get_side_count(),
-            image.get_title()
+            self.floppy.get_track_count() * self.floppy.get_side_count(),
+            self.floppy.get_title()
         );
-        self.floppy = image;
         self.floppy_inserted = true;
-        Ok(())
+        self.inserting = None;
     }
 
-    /// Gets the spindle motor speed in rounds/minute for the currently selected track
+    /// Sets the spindle motor speed adjustment, in rounds/minute. The adjustment is
+    /// clamped to RPM_ADJUSTMENT_MAX.
+    pub fn set_rpm_adjustment(&mut self, adjustment: i32) {
+        self.rpm_adjustment = adjustment.clamp(-Self::RPM_ADJUSTMENT_MAX, Self::RPM_ADJUSTMENT_MAX);
+    }
+
+    /// Gets the spindle motor speed adjustment, in rounds/minute
+    pub fn get_rpm_adjustment(&self) -> i32 {
+        self.rpm_adjustment
+    }
+
+    /// Gets the spindle motor speed in rounds/minute for the currently selected track,
+    /// including the user adjustment.
     pub fn get_track_rpm(&self) -> Ticks {
+        let rpm = self.get_nominal_track_rpm();
+        if rpm == 0 {
+            // Motor stopped
+            return 0;
+        }
+        // Never adjust a spinning motor down to a stop
+        rpm.saturating_add_signed(self.rpm_adjustment as isize)
+            .max(1)
+    }
+
+    /// Gets the nominal spindle motor speed in rounds/minute for the currently selected track
+    fn get_nominal_track_rpm(&self) -> Ticks {
         if self.mfm {
             // SuperDrive in MFM mode, fixed speed (CAV)
             // TODO DD spins at 600rpm!
@@ -489,6 +577,8 @@ impl FloppyDrive {
         info!("Drive {}: disk ejected", self.idx);
         self.floppy_inserted = false;
         self.ejecting = None;
+        self.inserting = None;
+        self.switched = false;
         self.mfm = self.drive_type.io_mfm();
     }
 }
@@ -545,4 +635,69 @@ mod tests {
         // Roughly is good enough..
         assert_eq!(result / 10, DISK_RPM_INNER * 120 / 10);
     }
+
+    #[test]
+    fn disk_rpm_adjustment() {
+        let mut drv = FloppyDrive::new(0, DriveType::GCR800K);
+        drv.floppy_inserted = true;
+        drv.track = 0;
+        drv.set_rpm_adjustment(10);
+        assert_eq!(drv.get_track_rpm(), DISK_RPM_OUTER + 10);
+        drv.set_rpm_adjustment(-10);
+        assert_eq!(drv.get_track_rpm(), DISK_RPM_OUTER - 10);
+
+        // Out of range
+        drv.set_rpm_adjustment(i32::MIN);
+        assert_eq!(drv.get_rpm_adjustment(), -FloppyDrive::RPM_ADJUSTMENT_MAX);
+        assert_ne!(drv.get_ticks_per_bit(), Ticks::MAX);
+    }
+
+    #[test]
+    fn disk_compatibility() {
+        let mut drv = FloppyDrive::new(0, DriveType::GCR800K);
+        assert!(drv
+            .disk_insert(FloppyImage::new(FloppyType::Mfm720K, ""))
+            .is_err());
+        assert!(!drv.floppy_inserted);
+        assert!(drv
+            .disk_insert(FloppyImage::new(FloppyType::Mac800K, ""))
+            .is_ok());
+
+        let mut drv = FloppyDrive::new(0, DriveType::SuperDrive);
+        assert!(drv
+            .disk_insert(FloppyImage::new(FloppyType::Mfm720K, ""))
+            .is_ok());
+    }
+
+    #[test]
+    fn disk_swap() {
+        let cistn = DriveReg::CISTN as u8;
+        let switched = DriveReg::SWITCHED as u8;
+
+        let mut drv = FloppyDrive::new(0, DriveType::GCR800K);
+        drv.disk_insert(FloppyImage::new(FloppyType::Mac800K, "First"))
+            .unwrap();
+        assert!(!drv.read_sense(cistn));
+        assert!(!drv.read_sense(switched));
+
+        // Swapping ejects the first disk and holds off inserting the second
+        drv.disk_insert(FloppyImage::new(FloppyType::Mac400K, "Second"))
+            .unwrap();
+        assert!(drv.read_sense(cistn));
+        assert!(drv.read_sense(switched));
+
+        drv.cycles += FloppyDrive::SWAP_DELAY - 1;
+        drv.update_insert();
+        assert!(drv.read_sense(cistn));
+
+        drv.cycles += 1;
+        drv.update_insert();
+        assert!(!drv.read_sense(cistn));
+        assert!(drv.read_sense(switched));
+        assert_eq!(drv.floppy.get_title(), "Second");
+
+        // Ejecting by the Mac resets the switched state
+        drv.eject();
+        assert!(!drv.read_sense(switched));
+    }
 }
@@ -119,6 +119,8 @@ impl IsmRegister {
 pub(super) enum IsmFifoEntry {
     Marker(u8),
     Data(u8),
+    /// CRC to write, takes one FIFO entry but writes two bytes
+    Crc(u16),
 }
 
 impl Swim {
@@ -135,10 +137,58 @@ impl Swim {
         out
     }
 
+    /// Encodes a byte to MFM, inserting clock bits where needed
+    fn ism_mfm_encode(&mut self, data: u8) -> u16 {
+        let mut out = 0;
+        for i in (0..8).rev() {
+            let bit = data & (1 << i) != 0;
+            let clock = !self.ism_write_last && !bit;
+            out = (out << 2) | (u16::from(clock) << 1) | u16::from(bit);
+            self.ism_write_last = bit;
+        }
+        out
+    }
+
+    /// Updates a CRC-16-CCITT with a byte, as used by MFM sector headers and data
+    fn ism_crc_update(crc: u16, data: u8) -> u16 {
+        let mut crc = crc ^ (u16::from(data) << 8);
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    }
+
+    /// Queues a byte for writing to the disk
+    fn ism_fifo_push(&mut self, marker: bool, value: u8) {
+        if self.ism_fifo.len() >= 2 {
+            warn!("ISM FIFO overrun (CPU writing too fast)");
+            self.ism_error.set_overrun(true);
+            return;
+        }
+
+        if marker && !self.ism_crc_mark {
+            // CRC is preset by the first mark
+            self.ism_crc = 0xFFFF;
+        }
+        self.ism_crc_mark = marker;
+        self.ism_crc = Self::ism_crc_update(self.ism_crc, value);
+
+        self.ism_fifo.push_back(if marker {
+            IsmFifoEntry::Marker(value)
+        } else {
+            IsmFifoEntry::Data(value)
+        });
+    }
+
     fn ism_fifo_pop(&mut self, expect_marker: bool) -> Option<(bool, u8)> {
         match self.ism_fifo.pop_front()? {
             IsmFifoEntry::Data(d) => Some((false, d)),
             IsmFifoEntry::Marker(d) => Some((!expect_marker, d)),
+            IsmFifoEntry::Crc(c) => Some((false, (c >> 8) as u8)),
         }
     }
 
@@ -180,14 +230,18 @@ impl Swim {
                         )
                         .with_motoron(self.get_selected_drive().motor)
                         .with_error(self.ism_error.0 != 0)
-                        .with_fifo_two(
-                            // TODO write mode
-                            self.ism_fifo.len() >= 2,
-                        )
-                        .with_fifo_one(
-                            // TODO write mode
-                            !self.ism_fifo.is_empty(),
-                        )
+                        .with_fifo_two(if self.ism_mode.write() {
+                            // Room for two bytes
+                            self.ism_fifo.is_empty()
+                        } else {
+                            self.ism_fifo.len() >= 2
+                        })
+                        .with_fifo_one(if self.ism_mode.write() {
+                            // Room for one byte
+                            self.ism_fifo.len() < 2
+                        } else {
+                            !self.ism_fifo.is_empty()
+                        })
                         .0,
                 ),
                 IsmRegister::Parameter => {
@@ -218,13 +272,28 @@ impl Swim {
     pub(super) fn ism_write(&mut self, addr: Address, value: Byte) {
         let offset = (addr - 0xDFE1FF) / 512;
 
-        if let Some(reg) = IsmRegister::from(offset, false, true) {
+        if let Some(reg) = IsmRegister::from(offset, self.ism_mode.action(), true) {
             //debug!(
             //    "ISM write {:06X} {:02X} {:?}: {:02X}",
             //    addr, offset, reg, value
             //);
             match reg {
-                IsmRegister::Data | IsmRegister::Mark => (),
+                IsmRegister::Data | IsmRegister::Mark => {
+                    // The FIFO can be preloaded before setting action
+                    if self.ism_mode.write() {
+                        self.ism_fifo_push(matches!(reg, IsmRegister::Mark), value);
+                    }
+                }
+                IsmRegister::Crc => {
+                    if self.ism_mode.write() {
+                        if self.ism_fifo.len() >= 2 {
+                            warn!("ISM FIFO overrun (CPU writing too fast)");
+                            self.ism_error.set_overrun(true);
+                        } else {
+                            self.ism_fifo.push_back(IsmFifoEntry::Crc(self.ism_crc));
+                        }
+                    }
+                }
                 IsmRegister::Phase => self.ism_write_phases(value),
                 IsmRegister::ModeZero => {
                     self.ism_param_idx = 0;
@@ -242,14 +311,16 @@ impl Swim {
                 IsmRegister::ModeOne => {
                     let set = IsmStatus(value & !self.ism_mode.0);
                     if set.action() {
-                        if self.ism_mode.write() {
-                            error!("Entered write mode, TODO");
-                        }
-
                         // Entered read/write mode, reset sync/shifter
                         self.ism_synced = false;
                         self.ism_shreg = 0;
                         self.ism_shreg_cnt = 0;
+                        if self.ism_fifo.is_empty() {
+                            // Keep the CRC state of preloaded data
+                            self.ism_crc_mark = false;
+                        }
+                        self.ism_write_last = false;
+                        self.wp_write_reported = false;
                     }
                     self.ism_mode.0 |= value;
                 }
@@ -300,6 +371,54 @@ impl Swim {
         self.lstrb = outputs & (1 << 3) != 0;
     }
 
+    /// Writes the next bit cell from the write shifter to the disk
+    fn ism_tick_write(&mut self) {
+        let head = self.get_active_head();
+        self.get_selected_drive_mut().next_bit(head);
+
+        if self.ism_shreg_cnt == 0 {
+            self.ism_shreg = match self.ism_fifo.pop_front() {
+                Some(IsmFifoEntry::Data(v)) => self.ism_mfm_encode(v),
+                Some(IsmFifoEntry::Marker(v)) => {
+                    let encoded = self.ism_mfm_encode(v);
+                    if v == Self::ism_mfm_decode(Self::MFM_SYNC_MARKER) {
+                        // Sync mark, with a dropped clock bit
+                        Self::MFM_SYNC_MARKER
+                    } else {
+                        encoded
+                    }
+                }
+                Some(IsmFifoEntry::Crc(v)) => {
+                    // Low byte follows directly after
+                    self.ism_fifo.push_front(IsmFifoEntry::Data(v as u8));
+                    self.ism_mfm_encode((v >> 8) as u8)
+                }
+                None => {
+                    // Nothing left to write
+                    self.ism_error.set_underrun(true);
+                    return;
+                }
+            };
+            self.ism_shreg_cnt = 16;
+        }
+
+        let bit = self.ism_shreg & 0x8000 != 0;
+        self.ism_shreg <<= 1;
+        self.ism_shreg_cnt -= 1;
+
+        if self.get_selected_drive().floppy.get_write_protect() {
+            // Disk is write protected, discard the data and report it once per write
+            if !self.wp_write_reported {
+                let drive = self.get_selected_drive_idx();
+                warn!("Drive {}: write to write protected disk", drive);
+                self.wp_write_event[drive].set();
+                self.wp_write_reported = true;
+            }
+            return;
+        }
+        self.get_selected_drive_mut().write_bit(head, bit);
+    }
+
     pub(super) fn ism_tick(&mut self, _ticks: usize) -> Result<()> {
         // This is only called when the drive is active and running
         if !self.ism_mode.action()
@@ -317,6 +436,11 @@ impl Swim {
             return Ok(());
         }
 
+        if self.ism_mode.write() {
+            self.ism_tick_write();
+            return Ok(());
+        }
+
         let head = self.get_active_head();
         let bit = self.get_selected_drive_mut().next_bit(head);
         self.ism_shreg <<= 1;
@@ -359,3 +483,171 @@ impl Swim {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use snow_floppy::loaders::{FloppyImageLoader, RawImage};
+    use snow_floppy::{FloppyImage, FloppyType};
+
+    use super::*;
+    use crate::mac::swim::drive::DriveType;
+    use crate::tickable::{Tickable, TICKS_PER_SECOND};
+
+    /// Data register offset
+    const DATA: Address = 0;
+    /// Mark register offset
+    const MARK: Address = 1;
+    /// CRC register offset (write, in action mode)
+    const CRC: Address = 2;
+    /// Mode register (clear) offset
+    const MODE_ZERO: Address = 6;
+    /// Mode register (set) offset
+    const MODE_ONE: Address = 7;
+
+    /// Gets the I/O address of an ISM register
+    const fn reg(offset: Address) -> Address {
+        0xDFE1FF + offset * 512
+    }
+
+    fn swim() -> Swim {
+        let mut swim = Swim::new(&[DriveType::SuperDrive], true);
+        swim.disk_insert(0, FloppyImage::new(FloppyType::Mfm144M, ""))
+            .unwrap();
+        swim.drives[0].motor = true;
+        swim.mode = SwimMode::Ism;
+        swim
+    }
+
+    #[test]
+    fn crc() {
+        // ID field of track 0, side 0, sector 1, 512 bytes
+        let crc = [0xA1, 0xA1, 0xA1, 0xFE, 0x00, 0x00, 0x01, 0x02]
+            .into_iter()
+            .fold(0xFFFF, Swim::ism_crc_update);
+        assert_eq!(crc, 0xCA6F);
+    }
+
+    /// Writes a sector data field and reads it back. The first `preload` bytes
+    /// are written to the FIFO before setting action.
+    fn mfm_write_read_preload(preload: usize) {
+        let mut swim = swim();
+        let data = (0..=255).collect::<Vec<u8>>();
+
+        // Gap, sync marks, data mark, data, CRC and a trailing gap
+        let mut writes = vec![];
+        writes.extend([(DATA, 0x4E); 4]);
+        writes.extend([(DATA, 0x00); 12]);
+        writes.extend([(MARK, 0xA1); 3]);
+        writes.push((DATA, 0xFB));
+        writes.extend(data.iter().map(|&d| (DATA, d)));
+        writes.push((CRC, 0));
+        writes.extend([(DATA, 0x4E); 2]);
+
+        // Write
+        swim.ism_write(
+            reg(MODE_ONE),
+            IsmStatus(0).with_drive1_enable(true).with_write(true).0,
+        );
+        let mut writes = writes.into_iter().peekable();
+        for (r, v) in writes.by_ref().take(preload) {
+            swim.ism_write(reg(r), v);
+        }
+        assert_eq!(swim.ism_fifo.len(), preload);
+        swim.ism_write(reg(MODE_ONE), IsmStatus(0).with_action(true).0);
+        while let Some(&(r, v)) = writes.peek() {
+            if swim.ism_fifo.len() < 2 {
+                swim.ism_write(reg(r), v);
+                writes.next();
+            }
+            swim.tick(1).unwrap();
+        }
+        while !swim.ism_fifo.is_empty() || swim.ism_shreg_cnt > 0 {
+            swim.tick(1).unwrap();
+        }
+        assert_eq!(swim.ism_error.0, 0);
+
+        // Read back
+        let mut expected = vec![0xA1, 0xA1, 0xA1, 0xFB];
+        expected.extend(&data);
+        let crc = expected.iter().copied().fold(0xFFFF, Swim::ism_crc_update);
+        expected.extend(crc.to_be_bytes());
+
+        swim.ism_write(
+            reg(MODE_ZERO),
+            IsmStatus(0).with_write(true).with_action(true).0,
+        );
+        swim.ism_write(reg(MODE_ONE), IsmStatus(0).with_action(true).0);
+        let mut read = vec![];
+        for _ in 0..TICKS_PER_SECOND {
+            swim.tick(1).unwrap();
+            if !swim.ism_fifo.is_empty() {
+                read.push(swim.ism_read(reg(MARK)).unwrap());
+            }
+            if read.len() == expected.len() {
+                break;
+            }
+        }
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn mfm_write_read() {
+        mfm_write_read_preload(0);
+    }
+
+    #[test]
+    fn mfm_write_preload() {
+        mfm_write_read_preload(2);
+    }
+
+    /// Encodes a raw PC-format image and reads back the first sector through the ISM
+    fn mfm_read_encoded(floppytype: FloppyType) {
+        let data = (0..floppytype.get_logical_size())
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let mut swim = Swim::new(&[DriveType::SuperDrive], true);
+        swim.disk_insert(0, RawImage::load(&data, None).unwrap())
+            .unwrap();
+        swim.drives[0].motor = true;
+        swim.mode = SwimMode::Ism;
+
+        // ID field of track 0, side 0, sector 1, 512 bytes
+        let mut id = vec![0xA1, 0xA1, 0xA1, 0xFE, 0x00, 0x00, 0x01, 0x02];
+        let crc = id.iter().copied().fold(0xFFFF, Swim::ism_crc_update);
+        id.extend(crc.to_be_bytes());
+        // Data field
+        let mut field = vec![0xA1, 0xA1, 0xA1, 0xFB];
+        field.extend(&data[0..512]);
+        let crc = field.iter().copied().fold(0xFFFF, Swim::ism_crc_update);
+        field.extend(crc.to_be_bytes());
+        // ID field, gap 2, sync, data field
+        let mut expected = id;
+        expected.extend([0x4E; 22]);
+        expected.extend([0x00; 12]);
+        expected.extend(field);
+
+        swim.ism_write(reg(MODE_ONE), IsmStatus(0).with_drive1_enable(true).0);
+        swim.ism_write(reg(MODE_ONE), IsmStatus(0).with_action(true).0);
+        let mut read = vec![];
+        for _ in 0..TICKS_PER_SECOND {
+            swim.tick(1).unwrap();
+            if !swim.ism_fifo.is_empty() {
+                read.push(swim.ism_read(reg(MARK)).unwrap());
+            }
+            if read.len() == expected.len() {
+                break;
+            }
+        }
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn mfm_read_encoded_720k() {
+        mfm_read_encoded(FloppyType::Mfm720K);
+    }
+
+    #[test]
+    fn mfm_read_encoded_1440k() {
+        mfm_read_encoded(FloppyType::Mfm144M);
+    }
+}
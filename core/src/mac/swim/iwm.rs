@@ -321,6 +321,19 @@ impl Swim {
         let bit = self.get_selected_drive_mut().next_bit(head);
         self.iwm_shift_bit(bit);
 
+        if self.write_pos == 0
+            && self.write_buffer.is_some()
+            && self.get_selected_drive().floppy.get_write_protect()
+        {
+            // Disk is write protected, discard the data and report it once per write
+            self.write_buffer = None;
+            if !self.wp_write_reported {
+                let drive = self.get_selected_drive_idx();
+                warn!("Drive {}: write to write protected disk", drive);
+                self.wp_write_event[drive].set();
+                self.wp_write_reported = true;
+            }
+        }
         if self.write_pos == 0 && self.write_buffer.is_some() {
             // Write idle and new data in write FIFO, start writing 8 new bits
             let Some(v) = self.write_buffer else {
@@ -343,6 +356,11 @@ impl Swim {
     }
 
     pub(super) fn iwm_tick(&mut self, ticks: usize) -> Result<()> {
+        if !self.q7 {
+            // Left write mode
+            self.wp_write_reported = false;
+        }
+
         match self.get_selected_drive().floppy.get_track_type(
             self.get_active_head(),
             self.get_selected_drive().get_active_track(),
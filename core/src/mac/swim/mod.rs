@@ -11,11 +11,13 @@ use std::collections::VecDeque;
 
 use anyhow::{bail, Result};
 use ism::{IsmError, IsmSetup, IsmStatus};
+use log::*;
 
 use drive::{DriveType, FloppyDrive};
 use iwm::{IwmMode, IwmStatus};
 use snow_floppy::flux::FluxTicks;
-use snow_floppy::{Floppy, FloppyImage};
+use snow_floppy::loaders::{FloppyImageLoader, RawImage};
+use snow_floppy::{Floppy, FloppyImage, FloppyType};
 
 use crate::bus::{Address, BusMember};
 use crate::mac::swim::ism::IsmFifoEntry;
@@ -117,9 +119,21 @@ pub struct Swim {
     ism_shreg: u16,
     ism_synced: bool,
     ism_shreg_cnt: usize,
+    /// CRC of the data written in ISM write mode
+    ism_crc: u16,
+    /// Last written entry was a mark (for CRC preset)
+    ism_crc_mark: bool,
+    /// Last written data bit (for MFM clock bits)
+    ism_write_last: bool,
 
     pub(crate) drives: [FloppyDrive; 3],
 
+    /// Write attempted to a write protected disk, per drive
+    pub(crate) wp_write_event: [LatchingEvent; 3],
+
+    /// Write protect violation already reported for the current write
+    wp_write_reported: bool,
+
     pub dbg_pc: u32,
     pub dbg_break: LatchingEvent,
 }
@@ -166,8 +180,13 @@ impl Swim {
             ism_shreg: 0,
             ism_synced: false,
             ism_shreg_cnt: 0,
+            ism_crc: 0,
+            ism_crc_mark: false,
+            ism_write_last: false,
 
             enable: false,
+            wp_write_event: Default::default(),
+            wp_write_reported: false,
             dbg_pc: 0,
             dbg_break: LatchingEvent::default(),
         }
@@ -220,6 +239,50 @@ impl Swim {
         self.drives[drive].disk_insert(image)
     }
 
+    /// Replaces the disk in a drive with a blank disk of the specified type
+    pub fn disk_format(&mut self, drive: usize, floppy_type: FloppyType) -> Result<()> {
+        if !self.drives[drive].is_present() {
+            bail!("Drive {} not present", drive);
+        }
+        if self.drives[drive].floppy_inserted && self.drives[drive].floppy.get_write_protect() {
+            bail!("Disk in drive {} is write protected", drive);
+        }
+
+        // Low-level formatted disk, with all sectors present and zeroed
+        let image = RawImage::load(&vec![0; floppy_type.get_logical_size()], Some("Blank"))?;
+        self.drives[drive].disk_insert(image)
+    }
+
+    /// Sets or clears the write protect tab of the disk in a drive
+    pub fn set_write_protect(&mut self, drive: usize, wp: bool) -> Result<()> {
+        if !self.drives[drive].is_present() {
+            bail!("Drive {} not present", drive);
+        }
+        if !self.drives[drive].floppy_inserted {
+            bail!("No disk in drive {}", drive);
+        }
+
+        let floppy = &mut self.drives[drive].floppy;
+        floppy.set_force_wp(wp);
+        if !wp && floppy.get_write_protect() {
+            warn!(
+                "Drive {}: medium cannot be written to, remains write protected",
+                drive
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the spindle motor speed adjustment of a drive, in rounds/minute
+    pub fn set_rpm_adjustment(&mut self, drive: usize, adjustment: i32) -> Result<()> {
+        if !self.drives[drive].is_present() {
+            bail!("Drive {} not present", drive);
+        }
+
+        self.drives[drive].set_rpm_adjustment(adjustment);
+        Ok(())
+    }
+
     /// Gets the active (selected) drive head
     fn get_active_head(&self) -> usize {
         if !self.get_selected_drive().drive_type.is_doublesided()
@@ -254,6 +317,14 @@ impl Swim {
     pub fn get_active_image(&self, drive: usize) -> &FloppyImage {
         &self.drives[drive].floppy
     }
+
+    /// Gets the head position and spindle motor state of all present drives
+    pub(crate) fn drive_activity(&self) -> impl Iterator<Item = (usize, bool)> + '_ {
+        self.drives
+            .iter()
+            .filter(|d| d.is_present())
+            .map(|d| (d.track, d.is_running()))
+    }
 }
 
 impl BusMember<Address> for Swim {
@@ -281,6 +352,7 @@ impl Tickable for Swim {
         self.cycles += ticks;
         for drv in &mut self.drives {
             drv.cycles = self.cycles;
+            drv.update_insert();
         }
 
         // When an EJECT command is sent, do not actually eject the disk until eject strobe has been
@@ -313,3 +385,30 @@ impl Tickable for Swim {
         Ok(ticks)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_format() {
+        for floppy_type in [
+            FloppyType::Mac400K,
+            FloppyType::Mac800K,
+            FloppyType::Mfm720K,
+            FloppyType::Mfm144M,
+        ] {
+            let mut swim = Swim::new(&[DriveType::SuperDrive], true);
+            swim.disk_format(0, floppy_type).unwrap();
+
+            let image = &swim.drives[0].floppy;
+            assert_eq!(image.get_type(), floppy_type);
+            let sectors = image
+                .analyze()
+                .iter()
+                .map(|t| t.sector_count().unwrap())
+                .sum::<usize>();
+            assert_eq!(sectors, floppy_type.get_sector_count());
+        }
+    }
+}
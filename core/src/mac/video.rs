@@ -58,7 +58,7 @@ use std::sync::atomic::Ordering;
 
 use crate::{
     bus::Address,
-    renderer::Renderer,
+    renderer::{DisplayAdjust, MonochromePalette, Renderer},
     tickable::{Tickable, Ticks},
     types::LatchingEvent,
 };
@@ -81,6 +81,9 @@ pub struct Video<T: Renderer> {
     /// Latch for entered HBlank
     event_hblank: LatchingEvent,
 
+    /// Latch for a change of framebuffer contents
+    event_fb_change: LatchingEvent,
+
     /// Primary and alternate framebuffer
     pub framebuffers: [Vec<u8>; 2],
 
@@ -88,6 +91,15 @@ pub struct Video<T: Renderer> {
     /// (true = main, false = alternate)
     /// (lives in VIA, copied here)
     pub framebuffer_select: bool,
+
+    /// Output adjustment (brightness, contrast, gamma)
+    adjust: DisplayAdjust,
+
+    /// Output colors
+    palette: MonochromePalette,
+
+    /// RGB output color of a cleared (white) and set (black) pixel, after adjustment
+    colors: [[u8; 3]; 2],
 }
 
 impl<T> Video<T>
@@ -165,14 +177,46 @@ where
             dots: 0,
             event_vblank: LatchingEvent::default(),
             event_hblank: LatchingEvent::default(),
+            event_fb_change: LatchingEvent::default(),
             framebuffers: [
                 vec![0; Self::FRAMEBUFFER_SIZE],
                 vec![0; Self::FRAMEBUFFER_SIZE],
             ],
             framebuffer_select: false,
+            adjust: DisplayAdjust::default(),
+            palette: MonochromePalette::default(),
+            colors: MonochromePalette::default().colors(),
         }
     }
 
+    /// Recalculates the output colors after a change of palette or adjustment
+    fn update_colors(&mut self) {
+        let adjust = self.adjust;
+        self.colors = self.palette.colors().map(|c| c.map(|v| adjust.apply(v)));
+    }
+
+    /// Sets the brightness/contrast/gamma adjustment of the output
+    pub fn set_adjust(&mut self, adjust: DisplayAdjust) {
+        self.adjust = adjust;
+        self.update_colors();
+    }
+
+    /// Gets the brightness/contrast/gamma adjustment of the output
+    pub fn get_adjust(&self) -> DisplayAdjust {
+        self.adjust
+    }
+
+    /// Sets the colors of the output
+    pub fn set_palette(&mut self, palette: MonochromePalette) {
+        self.palette = palette;
+        self.update_colors();
+    }
+
+    /// Gets the colors of the output
+    pub fn get_palette(&self) -> MonochromePalette {
+        self.palette
+    }
+
     /// Reads and clears 'entered vblank' latch
     pub fn get_clr_vblank(&mut self) -> bool {
         self.event_vblank.get_clear()
@@ -183,6 +227,20 @@ where
         self.event_hblank.get_clear()
     }
 
+    /// Reads and clears 'framebuffer changed' latch
+    pub fn get_clr_fb_change(&mut self) -> bool {
+        self.event_fb_change.get_clear()
+    }
+
+    /// Writes a byte to one of the framebuffers (0 = main, 1 = alternate)
+    pub fn write_framebuffer(&mut self, page: usize, offset: usize, val: u8) {
+        let fb = &mut self.framebuffers[page];
+        if fb[offset] != val {
+            fb[offset] = val;
+            self.event_fb_change.set();
+        }
+    }
+
     /// Prepares the image and sends it to the frontend renderer
     fn render(&mut self) -> Result<()> {
         let fb = if !self.framebuffer_select {
@@ -195,15 +253,10 @@ where
         for idx in 0..Self::FRAME_VISIBLE_DOTS {
             let byte = idx / 8;
             let bit = idx % 8;
-            if fb[byte] & (1 << (7 - bit)) == 0 {
-                buf[idx * 4].store(0xEE, Ordering::Release);
-                buf[idx * 4 + 1].store(0xEE, Ordering::Release);
-                buf[idx * 4 + 2].store(0xEE, Ordering::Release);
-            } else {
-                buf[idx * 4].store(0x22, Ordering::Release);
-                buf[idx * 4 + 1].store(0x22, Ordering::Release);
-                buf[idx * 4 + 2].store(0x22, Ordering::Release);
-            }
+            let [r, g, b] = self.colors[usize::from(fb[byte] & (1 << (7 - bit)) != 0)];
+            buf[idx * 4].store(r, Ordering::Release);
+            buf[idx * 4 + 1].store(g, Ordering::Release);
+            buf[idx * 4 + 2].store(b, Ordering::Release);
         }
         self.renderer.update()?;
 
@@ -284,6 +337,56 @@ mod tests {
         assert_eq!(v.dots, 0);
     }
 
+    #[test]
+    fn adjust() {
+        let mut v = video();
+        assert_eq!(v.colors, [[0xEE; 3], [0x22; 3]]);
+
+        v.set_adjust(DisplayAdjust {
+            brightness: 0.1,
+            ..Default::default()
+        });
+        assert!(v.colors[0][0] > 0xEE && v.colors[1][0] > 0x22);
+
+        v.set_adjust(DisplayAdjust {
+            contrast: 2.0,
+            ..Default::default()
+        });
+        assert_eq!(v.colors, [[0xFF; 3], [0x00; 3]]);
+
+        v.set_adjust(DisplayAdjust::default());
+        assert_eq!(v.colors, [[0xEE; 3], [0x22; 3]]);
+    }
+
+    #[test]
+    fn palette() {
+        let mut v = video();
+        v.set_palette(MonochromePalette::Inverted);
+        assert_eq!(v.colors, [[0x22; 3], [0xEE; 3]]);
+
+        // Adjustment applies on top of the palette
+        v.set_adjust(DisplayAdjust {
+            contrast: 2.0,
+            ..Default::default()
+        });
+        assert_eq!(v.colors, [[0x00; 3], [0xFF; 3]]);
+    }
+
+    #[test]
+    fn fb_change() {
+        let mut v = video();
+        assert!(!v.get_clr_fb_change());
+
+        v.write_framebuffer(1, 10, 0xAA);
+        assert_eq!(v.framebuffers[1][10], 0xAA);
+        assert!(v.get_clr_fb_change());
+        assert!(!v.get_clr_fb_change());
+
+        // Writing the same value is not a change
+        v.write_framebuffer(1, 10, 0xAA);
+        assert!(!v.get_clr_fb_change());
+    }
+
     #[test]
     fn hblank_period() {
         let mut v = video();
@@ -15,6 +15,73 @@ pub fn new_displaybuffer(width: usize, height: usize) -> DisplayBuffer {
     ))
 }
 
+/// Colors of the display output of monochrome models
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, strum::Display)]
+pub enum MonochromePalette {
+    /// Black on white, like the original display
+    #[default]
+    Normal,
+    /// White on black
+    Inverted,
+    /// Green phosphor monitor
+    Green,
+    /// Amber phosphor monitor
+    Amber,
+}
+
+impl MonochromePalette {
+    /// Gets the RGB colors of a cleared (white) and set (black) pixel
+    pub const fn colors(self) -> [[u8; 3]; 2] {
+        match self {
+            Self::Normal => [[0xEE, 0xEE, 0xEE], [0x22, 0x22, 0x22]],
+            Self::Inverted => [[0x22, 0x22, 0x22], [0xEE, 0xEE, 0xEE]],
+            Self::Green => [[0x33, 0xFF, 0x66], [0x00, 0x1A, 0x08]],
+            Self::Amber => [[0xFF, 0xB0, 0x00], [0x1A, 0x10, 0x00]],
+        }
+    }
+}
+
+/// Brightness, contrast and gamma adjustment applied to the display output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayAdjust {
+    /// Added to the intensity (-1.0 - 1.0, 0.0 is unchanged)
+    pub brightness: f32,
+    /// Intensity is scaled around the midpoint by this factor (1.0 is unchanged)
+    pub contrast: f32,
+    /// Gamma correction (1.0 is unchanged)
+    pub gamma: f32,
+}
+
+impl Default for DisplayAdjust {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl DisplayAdjust {
+    /// Applies the adjustment to a single color component
+    pub fn apply(&self, value: u8) -> u8 {
+        if *self == Self::default() {
+            return value;
+        }
+
+        let v = f32::from(value) / 255.0;
+        let v = (v - 0.5)
+            .mul_add(self.contrast, 0.5 + self.brightness)
+            .clamp(0.0, 1.0);
+        let v = if self.gamma > 0.0 {
+            v.powf(1.0 / self.gamma)
+        } else {
+            v
+        };
+        (v * 255.0).round() as u8
+    }
+}
+
 pub trait Renderer {
     /// Creates a new renderer with a screen of the given size
     fn new(width: usize, height: usize) -> Result<Self>
@@ -1 +1,2 @@
 pub mod singlestep_m68k;
+pub mod timing_m68k;
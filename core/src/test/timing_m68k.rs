@@ -0,0 +1,100 @@
+//! Instruction timing tests for the M68000
+
+use crate::bus::testbus::Testbus;
+use crate::bus::{Address, Bus, ADDRESS_MASK};
+use crate::cpu_m68k::cpu::CpuM68k;
+use crate::cpu_m68k::regs::{RegisterFile, RegisterSR};
+use crate::tickable::Ticks;
+
+/// Address the code under test is placed at
+const CODE_ADDR: Address = 0x1000;
+
+/// NOP, used to pad the prefetch queue
+const NOP: u16 = 0x4E71;
+
+/// Executes a single instruction and returns the amount of cycles it took.
+///
+/// The CPU starts in supervisor mode with all registers cleared, the stack pointers
+/// at 0x8000 (SSP) and 0x9000 (USP) and the code at 0x1000. `setup` can be used to
+/// alter the register state before the instruction runs.
+pub fn run_instr(code: &[u16], setup: impl FnOnce(&mut RegisterFile)) -> Ticks {
+    let mut bus = Testbus::new(ADDRESS_MASK);
+    for (i, word) in code.iter().chain([NOP, NOP].iter()).enumerate() {
+        let addr = CODE_ADDR + (i as Address) * 2;
+        let [msb, lsb] = word.to_be_bytes();
+        bus.write(addr, msb);
+        bus.write(addr + 1, lsb);
+    }
+
+    let mut cpu = CpuM68k::new(bus);
+    cpu.trace_mask = true;
+    cpu.regs = RegisterFile::new();
+    cpu.regs.sr = RegisterSR(0x2700);
+    cpu.regs.ssp = 0x8000;
+    cpu.regs.usp = 0x9000;
+    setup(&mut cpu.regs);
+
+    // Prefetch queue holds the first two words, PC points at the first word
+    cpu.regs.pc = CODE_ADDR;
+    cpu.prefetch = [code[0], code.get(1).copied().unwrap_or(NOP)].into();
+
+    cpu.step().unwrap();
+    assert!(!cpu.step_exception, "Unexpected exception");
+    cpu.cycles
+}
+
+/// Builds an ASd/LSd/ROXd/ROd (register) opcode
+const fn shift_opcode(count_or_reg: u16, left: bool, size: u16, reg: bool, kind: u16) -> u16 {
+    0xE000
+        | ((count_or_reg & 7) << 9)
+        | ((left as u16) << 8)
+        | (size << 6)
+        | ((reg as u16) << 5)
+        | (kind << 3)
+}
+
+#[test]
+fn nop() {
+    assert_eq!(run_instr(&[NOP], |_| ()), 4);
+}
+
+#[test]
+fn move_w_indirect() {
+    // MOVE.w (A0),D0
+    assert_eq!(run_instr(&[0x3010], |r| r.a[0] = 0x2000), 8);
+}
+
+#[test]
+fn shift_immediate() {
+    // ASL.w #n,D0 / ASL.l #n,D0: 6+2n / 8+2n
+    for n in 1..=8 {
+        assert_eq!(
+            run_instr(&[shift_opcode(n, true, 0b01, false, 0b00)], |_| ()),
+            6 + 2 * n as Ticks
+        );
+        assert_eq!(
+            run_instr(&[shift_opcode(n, true, 0b10, false, 0b00)], |_| ()),
+            8 + 2 * n as Ticks
+        );
+    }
+}
+
+#[test]
+fn shift_register() {
+    // LSR.b D1,D0 / LSL.l D1,D0, count is taken modulo 64
+    let lsr_b = shift_opcode(1, false, 0b00, true, 0b01);
+    let lsl_l = shift_opcode(1, true, 0b10, true, 0b01);
+
+    for (count, n) in [
+        (0, 0),
+        (1, 1),
+        (17, 17),
+        (63, 63),
+        (64, 0),
+        (0xFFFF_FF41, 1),
+    ] {
+        let set_count = |r: &mut RegisterFile| r.d[1] = count;
+        assert_eq!(run_instr(&[lsr_b], set_count), 6 + 2 * n, "LSR.b {}", count);
+        assert_eq!(run_instr(&[lsl_l], set_count), 8 + 2 * n, "LSL.l {}", count);
+    }
+}
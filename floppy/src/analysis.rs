@@ -0,0 +1,129 @@
+//! Read-only analysis of the track structure of a floppy image
+
+use crate::{Floppy, FloppyImage, OriginalTrackType, TrackLength, TrackType};
+
+/// GCR address mark prologue
+const GCR_ADDRESS_MARK: u32 = 0xD5AA96;
+
+/// MFM A1 sync with missing clock, three times (raw bitstream)
+const MFM_SYNC: u64 = 0x4489_4489_4489;
+
+/// MFM ID address mark (decoded)
+const MFM_IDAM: u8 = 0xFE;
+
+/// Structure of a single track, as found by [`FloppyImage::analyze`]
+#[derive(Debug, Clone)]
+pub struct TrackAnalysis {
+    pub side: usize,
+    pub track: usize,
+    /// Type of the track as it was loaded from the image
+    pub original_type: OriginalTrackType,
+    /// Current type of the track
+    pub track_type: TrackType,
+    /// Length in bits (bitstream) or flux transitions (flux)
+    pub length: TrackLength,
+    /// Bit positions of the sector address marks on the track.
+    /// Flux tracks are not decoded, so this is always empty for those.
+    pub sector_marks: Vec<usize>,
+}
+
+impl TrackAnalysis {
+    /// Amount of sectors found on the track, None for flux tracks
+    pub fn sector_count(&self) -> Option<usize> {
+        match self.track_type {
+            TrackType::Bitstream => Some(self.sector_marks.len()),
+            TrackType::Flux => None,
+        }
+    }
+}
+
+impl FloppyImage {
+    /// Analyzes the structure of all tracks on the image
+    pub fn analyze(&self) -> Vec<TrackAnalysis> {
+        let mut result = Vec::with_capacity(self.get_side_count() * self.get_track_count());
+
+        for track in 0..self.get_track_count() {
+            for side in 0..self.get_side_count() {
+                let track_type = self.get_track_type(side, track);
+                let length = self.get_track_length(side, track);
+                let sector_marks = match length {
+                    TrackLength::Bits(0) | TrackLength::Transitions(_) => vec![],
+                    TrackLength::Bits(len) if self.floppy_type.is_mfm() => {
+                        self.find_marks_mfm(side, track, len)
+                    }
+                    TrackLength::Bits(len) => self.find_marks_gcr(side, track, len),
+                };
+
+                result.push(TrackAnalysis {
+                    side,
+                    track,
+                    original_type: self.get_original_track_type(side, track),
+                    track_type,
+                    length,
+                    sector_marks,
+                });
+            }
+        }
+
+        result
+    }
+
+    /// Finds GCR address marks on a bitstream track, as the disk controller would:
+    /// bits are shifted in until the MSB is set, which completes a byte.
+    ///
+    /// Scanning continues past the end of the track so marks crossing the index are
+    /// found too.
+    fn find_marks_gcr(&self, side: usize, track: usize, len: usize) -> Vec<usize> {
+        let mut marks = vec![];
+        let mut shifter = 0u8;
+        let mut window = 0u32;
+
+        for pos in 0..(len + 64) {
+            shifter = (shifter << 1) | u8::from(self.get_track_bit(side, track, pos % len));
+            if shifter & 0x80 == 0 {
+                continue;
+            }
+
+            window = ((window << 8) | u32::from(shifter)) & 0xFF_FFFF;
+            shifter = 0;
+
+            if window == GCR_ADDRESS_MARK {
+                // Walk back to the start of the mark. Sync bytes may be longer than 8
+                // bits, but the mark bytes themselves are always 8.
+                let start = (pos + 1 - 24) % len;
+                if !marks.contains(&start) {
+                    marks.push(start);
+                }
+            }
+        }
+
+        marks.sort_unstable();
+        marks
+    }
+
+    /// Finds MFM ID address marks (A1 A1 A1 FE) on a bitstream track
+    fn find_marks_mfm(&self, side: usize, track: usize, len: usize) -> Vec<usize> {
+        let mut marks = vec![];
+        let mut shifter = 0u64;
+
+        for pos in 0..(len + 64) {
+            shifter = (shifter << 1) | u64::from(self.get_track_bit(side, track, pos % len));
+            if pos < 63 || (shifter >> 16) & 0xFFFF_FFFF_FFFF != MFM_SYNC {
+                continue;
+            }
+
+            // Data bits are every second bit, after the clock bits
+            let raw = shifter as u16;
+            let data = (0..8).fold(0u8, |a, i| (a << 1) | ((raw >> (14 - i * 2)) & 1) as u8);
+            if data == MFM_IDAM {
+                let start = (pos + 1 - 64) % len;
+                if !marks.contains(&start) {
+                    marks.push(start);
+                }
+            }
+        }
+
+        marks.sort_unstable();
+        marks
+    }
+}
@@ -0,0 +1,130 @@
+//! Floppy image inspection and conversion tool
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use snow_floppy::loaders::{
+    Autodetect, Bitfile, Diskcopy42, FloppyImageLoader, FloppyImageSaver, Moof, RawImage,
+};
+use snow_floppy::{Floppy, OriginalTrackType, TrackLength, TrackType};
+use strum::IntoEnumIterator;
+
+use std::path::Path;
+
+#[derive(Eq, PartialEq, Clone, Copy, clap::ValueEnum)]
+/// Output image format
+enum OutputFormat {
+    /// Applesauce MOOF
+    Moof,
+    /// Apple DiskCopy 4.2, sector-based
+    Dc42,
+    /// Raw sector data
+    Raw,
+    /// Bitfile (development format)
+    Bitfile,
+}
+
+#[derive(Parser)]
+#[command(
+    about = "Snow - floppy image tool",
+    author = "Thomas <thomas@thomasw.dev>",
+    long_about = None)]
+struct Args {
+    /// Input image filename, the format is detected automatically
+    input: String,
+
+    /// Output image filename
+    output: Option<String>,
+
+    /// Output image format
+    #[arg(long, value_enum, default_value_t=OutputFormat::Moof)]
+    format: OutputFormat,
+
+    /// Print the structure of every track
+    #[arg(long)]
+    tracks: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let data = std::fs::read(&args.input)?;
+    let imagetype = Autodetect::detect(&data)?;
+    let img = Autodetect::load(
+        &data,
+        Path::new(&args.input).file_name().and_then(|s| s.to_str()),
+    )?;
+
+    println!("Image format: {}", imagetype.as_friendly_str());
+    println!("Floppy type : {}", img.get_type());
+    println!("Title       : {}", img.get_title());
+
+    let analysis = img.analyze();
+    for origtype in OriginalTrackType::iter() {
+        let count = analysis
+            .iter()
+            .filter(|t| t.original_type == origtype)
+            .count();
+        if count > 0 {
+            let name = format!("{:?}", origtype);
+            println!("{:<12}: {} tracks", name, count);
+        }
+    }
+
+    if args.tracks {
+        println!();
+        println!("Side Track Original  Length               Sectors");
+        for t in &analysis {
+            let original = format!("{:?}", t.original_type);
+            let length = match t.length {
+                TrackLength::Bits(b) => format!("{} bits", b),
+                TrackLength::Transitions(f) => format!("{} transitions", f),
+            };
+            let sectors = t
+                .sector_count()
+                .map_or_else(|| "-".to_string(), |c| c.to_string());
+            println!(
+                "{:<4} {:<5} {:<9} {:<20} {}",
+                t.side, t.track, original, length, sectors
+            );
+        }
+        println!();
+    }
+
+    let sectors = analysis
+        .iter()
+        .filter_map(|t| t.sector_count())
+        .sum::<usize>();
+    let flux_tracks = analysis
+        .iter()
+        .filter(|t| t.track_type == TrackType::Flux)
+        .count();
+    println!(
+        "Sectors     : {} found, {} expected",
+        sectors,
+        img.get_type().get_sector_count()
+    );
+    if flux_tracks > 0 {
+        println!(
+            "{} flux track(s) were not decoded, sectors on these tracks are not counted",
+            flux_tracks
+        );
+    }
+
+    let Some(output) = args.output else {
+        return Ok(());
+    };
+    match args.format {
+        OutputFormat::Moof => Moof::save_file(&img, &output)?,
+        OutputFormat::Dc42 => Diskcopy42::save_file(&img, &output)?,
+        OutputFormat::Raw => RawImage::save_file(&img, &output)?,
+        OutputFormat::Bitfile => {
+            if flux_tracks > 0 {
+                bail!("Bitfile cannot store flux tracks");
+            }
+            Bitfile::save_file(&img, &output)?;
+        }
+    }
+    println!("Written to {}", output);
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
+pub mod analysis;
 pub mod flux;
 pub mod loaders;
 mod macformat;
+mod mfmformat;
 
 use std::collections::HashMap;
 
@@ -19,12 +21,14 @@ pub enum FloppyType {
     Mac400K,
     /// Macintosh GCR CLV 3.5", double sided
     Mac800K,
+    /// MFM CAV 3.5", double sided, double density
+    Mfm720K,
     /// MFM CAV 3.5", double sided, high density
     Mfm144M,
 }
 
 /// Type of the original track when loaded from the image
-#[derive(Copy, Clone, EnumIter, Default, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, EnumIter, Default, Eq, PartialEq)]
 pub enum OriginalTrackType {
     /// Unknown
     #[default]
@@ -68,10 +72,17 @@ impl FloppyType {
                 64..=79 => 49760,
                 _ => unreachable!(),
             },
-            Self::Mfm144M => 192992,
+            // 300 rpm at 500 (DD) or 1000 (HD) kcells/s
+            Self::Mfm720K => 100000,
+            Self::Mfm144M => 200000,
         }
     }
 
+    /// Tests if this is an MFM-encoded (PC-style) format
+    pub fn is_mfm(self) -> bool {
+        matches!(self, Self::Mfm720K | Self::Mfm144M)
+    }
+
     /// Gets the sector size for this format
     pub fn get_sector_size(self) -> usize {
         512
@@ -87,6 +98,7 @@ impl FloppyType {
         match self {
             Self::Mac400K => 400 * 1024,
             Self::Mac800K => 800 * 1024,
+            Self::Mfm720K => 720 * 1024,
             Self::Mfm144M => 1440 * 1024,
         }
     }
@@ -100,6 +112,7 @@ impl std::fmt::Display for FloppyType {
             match self {
                 Self::Mac400K => "Macintosh GCR 400KB",
                 Self::Mac800K => "Macintosh GCR 800KB",
+                Self::Mfm720K => "MFM 720KB",
                 Self::Mfm144M => "MFM 1.44MB",
             }
         )
@@ -165,6 +178,9 @@ pub struct FloppyImage {
 
     /// Key/value store of metadata
     metadata: FloppyMetadata,
+
+    /// Write protect forced by the user
+    force_wp: bool,
 }
 
 impl FloppyImage {
@@ -195,9 +211,15 @@ impl FloppyImage {
             title: title.to_owned(),
             metadata: FloppyMetadata::from([("title".to_string(), title.to_string())]),
             origtracktype: [[Default::default(); FLOPPY_MAX_TRACKS]; FLOPPY_MAX_SIDES],
+            force_wp: false,
         }
     }
 
+    /// Forces the image to be write protected, regardless of the medium
+    pub fn set_force_wp(&mut self, wp: bool) {
+        self.force_wp = wp;
+    }
+
     /// Resizes the length of a track to the actual size used in the image
     pub(crate) fn set_actual_track_length(&mut self, side: usize, track: usize, sz: usize) {
         let TrackLength::Bits(old_sz) = self.get_track_length(side, track) else {
@@ -300,6 +322,7 @@ impl Floppy for FloppyImage {
         match self.floppy_type {
             FloppyType::Mac400K => 1,
             FloppyType::Mac800K => 2,
+            FloppyType::Mfm720K => 2,
             FloppyType::Mfm144M => 2,
         }
     }
@@ -313,8 +336,10 @@ impl Floppy for FloppyImage {
     }
 
     fn get_write_protect(&self) -> bool {
-        self.flux_trackdata
-            .iter()
-            .any(|s| s.iter().any(|t| !t.is_empty()))
+        self.force_wp
+            || self
+                .flux_trackdata
+                .iter()
+                .any(|s| s.iter().any(|t| !t.is_empty()))
     }
 }
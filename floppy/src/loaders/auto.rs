@@ -3,7 +3,7 @@
 #[cfg(feature = "fluxfox")]
 use crate::loaders::fluxfox::Fluxfox;
 use crate::loaders::{
-    A2Rv2, A2Rv3, Bitfile, Diskcopy42, FloppyImageLoader, Moof, RawImage, PFI, PRI,
+    A2Rv2, A2Rv3, Bitfile, Diskcopy42, FloppyImageLoader, Moof, RawImage, TwoImg, PFI, PRI,
 };
 use crate::{FloppyImage, FloppyType};
 
@@ -22,10 +22,12 @@ pub enum ImageType {
     PFI,
     PRI,
     Raw,
+    TwoImg,
 }
 
 impl ImageType {
-    pub const EXTENSIONS: [&'static str; 7] = ["a2r", "moof", "dc42", "pfi", "pri", "raw", "img"];
+    pub const EXTENSIONS: [&'static str; 8] =
+        ["a2r", "moof", "dc42", "pfi", "pri", "raw", "img", "2mg"];
 
     pub fn as_friendly_str(&self) -> &'static str {
         match self {
@@ -38,6 +40,7 @@ impl ImageType {
             Self::PFI => "PCE PFI",
             Self::PRI => "PCE PRI",
             Self::Raw => "Raw image",
+            Self::TwoImg => "2IMG",
         }
     }
 }
@@ -66,6 +69,10 @@ impl Autodetect {
         if data.len() >= 4 && data[0..4] == *b"PRI " {
             return Ok(ImageType::PRI);
         }
+        // 2IMG
+        if data.len() >= 4 && data[0..4] == *b"2IMG" {
+            return Ok(ImageType::TwoImg);
+        }
         // Apple DiskCopy 4.2
         if data.len() > 0x53 && data[0x52..=0x53] == [0x01, 0x00] {
             return Ok(ImageType::DC42);
@@ -103,6 +110,7 @@ impl FloppyImageLoader for Autodetect {
             ImageType::PFI => PFI::load(data, filename),
             ImageType::PRI => PRI::load(data, filename),
             ImageType::Raw => RawImage::load(data, filename),
+            ImageType::TwoImg => TwoImg::load(data, filename),
             ImageType::Fluxfox => {
                 #[cfg(feature = "fluxfox")]
                 {
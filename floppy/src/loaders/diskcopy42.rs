@@ -2,13 +2,13 @@
 //! Sector-based image format
 //! https://www.discferret.com/wiki/Apple_DiskCopy_4.2
 
-use super::FloppyImageLoader;
-use crate::macformat::MacFormatEncoder;
-use crate::{FloppyImage, FloppyType};
+use super::{FloppyImageLoader, FloppyImageSaver};
+use crate::macformat::{MacFormatDecoder, MacFormatEncoder};
+use crate::{Floppy, FloppyImage, FloppyType};
 
 use anyhow::{bail, Result};
 use binrw::io::Cursor;
-use binrw::{binrw, BinRead};
+use binrw::{binrw, BinRead, BinWrite};
 use log::*;
 
 #[binrw]
@@ -90,9 +90,22 @@ impl Dc42Raw {
     }
 }
 
-/// Apple DiskCopy 4.2 image loader
+/// Apple DiskCopy 4.2 image loader/saver
 pub struct Diskcopy42 {}
 
+impl Diskcopy42 {
+    /// Maximum length of the image name
+    const NAME_MAX: usize = 63;
+
+    /// DiskCopy checksum: add each big endian word and rotate right
+    fn checksum(data: &[u8]) -> u32 {
+        data.chunks(2).fold(0u32, |sum, w| {
+            sum.wrapping_add(u32::from(u16::from_be_bytes([w[0], w[1]])))
+                .rotate_right(1)
+        })
+    }
+}
+
 impl FloppyImageLoader for Diskcopy42 {
     fn load(data: &[u8], filename: Option<&str>) -> Result<FloppyImage> {
         let mut cursor = Cursor::new(data);
@@ -119,3 +132,61 @@ impl FloppyImageLoader for Diskcopy42 {
         }
     }
 }
+
+impl FloppyImageSaver for Diskcopy42 {
+    fn write(img: &FloppyImage, w: &mut impl std::io::Write) -> Result<()> {
+        let (encoding, format) = match img.get_type() {
+            FloppyType::Mac400K => (Dc42Encoding::GcrClvSsDd, Dc42Format::Mac400K),
+            FloppyType::Mac800K => (Dc42Encoding::GcrClvDsDd, Dc42Format::Mac800K),
+            t => bail!("Disk type {} not supported by DiskCopy 4.2", t),
+        };
+        let (data, mut tags) = MacFormatDecoder::decode(img)?;
+        if tags.iter().all(|&b| b == 0) {
+            tags.clear();
+        }
+
+        let mut name = img.get_title().to_string();
+        while name.len() > Self::NAME_MAX {
+            name.pop();
+        }
+
+        let mut out = Cursor::new(vec![]);
+        Dc42Raw {
+            name,
+            data_crc: Self::checksum(&data),
+            // The first sector's tag is not included in the checksum
+            tag_crc: tags.get(12..).map_or(0, Self::checksum),
+            encoding,
+            format,
+            data,
+            tags,
+        }
+        .write(&mut out)?;
+        w.write_all(out.get_ref())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macformat::MacFormatDecoder;
+
+    #[test]
+    fn save() {
+        let data = (0..FloppyType::Mac800K.get_logical_size())
+            .map(|i| (i % 249) as u8)
+            .collect::<Vec<_>>();
+        let img = MacFormatEncoder::encode(FloppyType::Mac800K, &data, None, "dc42").unwrap();
+        let saved = Diskcopy42::save_vec(&img).unwrap();
+        let loaded = Diskcopy42::load(&saved, None).unwrap();
+
+        assert_eq!(loaded.get_type(), FloppyType::Mac800K);
+        assert_eq!(loaded.get_title(), "dc42");
+        assert_eq!(MacFormatDecoder::decode(&loaded).unwrap().0, data);
+
+        let raw = Dc42Raw::read(&mut Cursor::new(&saved)).unwrap();
+        assert_eq!(raw.data_crc, Diskcopy42::checksum(&data));
+        assert!(raw.tags.is_empty());
+    }
+}
@@ -3,14 +3,14 @@
 //! https://applesaucefdc.com/moof-reference/
 
 use std::collections::HashMap;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom, Write};
 
-use super::FloppyImageLoader;
-use crate::{Floppy, FloppyImage, FloppyType, OriginalTrackType};
+use super::{FloppyImageLoader, FloppyImageSaver};
+use crate::{Floppy, FloppyImage, FloppyType, OriginalTrackType, TrackLength};
 
 use anyhow::{bail, Context, Result};
 use binrw::io::Cursor;
-use binrw::{binrw, BinRead};
+use binrw::{binrw, BinRead, BinWrite};
 use log::*;
 
 /// Initial MOOF file header
@@ -133,10 +133,30 @@ impl MoofChunkTrksEntry {
     }
 }
 
-/// Applesauce MOOF image file loader
+/// Applesauce MOOF image file loader/saver
 pub struct Moof {}
 
 impl Moof {
+    /// Size of the INFO chunk
+    const INFO_SIZE: usize = 60;
+
+    /// Block where the track data starts, right after the TRKS entries
+    const TRACKDATA_START_BLK: usize = 3;
+
+    /// Encodes a flux track, transitions of 255 ticks or longer are split up
+    fn encode_flux(transitions: &[i16]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(transitions.len());
+        for &t in transitions {
+            let mut t = t.max(0) as usize;
+            while t >= 255 {
+                out.push(255);
+                t -= 255;
+            }
+            out.push(t as u8);
+        }
+        out
+    }
+
     fn parse_meta(meta: &str) -> HashMap<&str, &str> {
         let mut result = HashMap::new();
 
@@ -270,3 +290,202 @@ impl FloppyImageLoader for Moof {
         Ok(img)
     }
 }
+
+impl FloppyImageSaver for Moof {
+    fn write(img: &FloppyImage, w: &mut impl std::io::Write) -> Result<()> {
+        let disktype = match img.get_type() {
+            FloppyType::Mac400K => MoofDiskType::SSDDGCR400k,
+            FloppyType::Mac800K => MoofDiskType::DSDDGCR800k,
+            FloppyType::Mfm144M => MoofDiskType::DSHDMFM144Mb,
+            t => bail!("Disk type {} not supported by MOOF", t),
+        };
+
+        let mut tmap = MoofChunkTmap {
+            tracks: [[255; 2]; 80],
+        };
+        let mut fluxmap = MoofChunkTmap {
+            tracks: [[255; 2]; 80],
+        };
+        let mut trks = MoofChunkTrks {
+            entries: core::array::from_fn(|_| MoofChunkTrksEntry {
+                start_blk: 0,
+                blocks: 0,
+                bits_bytes: 0,
+            }),
+        };
+        let mut trackdata = vec![];
+        let mut entry_idx = 0;
+        let mut largest_track = 0;
+        let mut largest_flux_track = 0;
+
+        // Collect track data, each track starts at a 512 byte block
+        for (track, side) in (0..80).flat_map(|t| (0..img.get_side_count()).map(move |s| (t, s))) {
+            let (data, bits_bytes) = match img.get_track_length(side, track) {
+                TrackLength::Bits(0) => continue,
+                TrackLength::Bits(bits) => {
+                    let data = &img.trackdata[side][track][..bits.div_ceil(8)];
+                    tmap.tracks[track][side] = entry_idx as u8;
+                    largest_track = largest_track.max(data.len().div_ceil(512));
+                    (data.to_vec(), bits)
+                }
+                TrackLength::Transitions(_) => {
+                    let data = Self::encode_flux(&img.flux_trackdata[side][track]);
+                    fluxmap.tracks[track][side] = entry_idx as u8;
+                    largest_flux_track = largest_flux_track.max(data.len().div_ceil(512));
+                    let len = data.len();
+                    (data, len)
+                }
+            };
+            let blocks = data.len().div_ceil(512);
+
+            trks.entries[entry_idx] = MoofChunkTrksEntry {
+                start_blk: (Self::TRACKDATA_START_BLK + trackdata.len() / 512).try_into()?,
+                blocks: blocks.try_into()?,
+                bits_bytes: bits_bytes.try_into()?,
+            };
+            trackdata.extend_from_slice(&data);
+            trackdata.resize(trackdata.len().next_multiple_of(512), 0);
+            entry_idx += 1;
+        }
+
+        let has_flux = largest_flux_track > 0;
+        let info = MoofChunkInfo {
+            version: 1,
+            disktype,
+            writeprotect: img.get_write_protect().into(),
+            synchronized: 0,
+            optimal_bit_timing: if img.get_type().is_mfm() { 8 } else { 16 },
+            creator: format!("{:<32}", "Snow"),
+            zero: 0,
+            largest_track: largest_track.try_into()?,
+            // The FLUX chunk is placed at the first block after the track data
+            flux_block: if has_flux {
+                (Self::TRACKDATA_START_BLK + trackdata.len() / 512).try_into()?
+            } else {
+                0
+            },
+            flux_longest_track: largest_flux_track.try_into()?,
+        };
+
+        // Everything after the file header, for the checksum
+        let mut body = Cursor::new(vec![]);
+        MoofChunkHeader {
+            id: *b"INFO",
+            size: Self::INFO_SIZE as u32,
+        }
+        .write(&mut body)?;
+        let info_start = body.position();
+        info.write(&mut body)?;
+        body.get_mut()
+            .resize(info_start as usize + Self::INFO_SIZE, 0);
+        body.seek(SeekFrom::End(0))?;
+
+        MoofChunkHeader {
+            id: *b"TMAP",
+            size: 160,
+        }
+        .write(&mut body)?;
+        tmap.write(&mut body)?;
+
+        MoofChunkHeader {
+            id: *b"TRKS",
+            size: (160 * 8 + trackdata.len()).try_into()?,
+        }
+        .write(&mut body)?;
+        trks.write(&mut body)?;
+        assert_eq!(
+            body.position() as usize + 12,
+            Self::TRACKDATA_START_BLK * 512
+        );
+        body.write_all(&trackdata)?;
+
+        if has_flux {
+            MoofChunkHeader {
+                id: *b"FLUX",
+                size: 160,
+            }
+            .write(&mut body)?;
+            fluxmap.write(&mut body)?;
+        }
+
+        let mut metadata = img.get_metadata().into_iter().collect::<Vec<_>>();
+        metadata.sort();
+        let meta = metadata
+            .into_iter()
+            .map(|(k, v)| format!("{}\t{}\n", k, v))
+            .collect::<String>();
+        MoofChunkHeader {
+            id: *b"META",
+            size: meta.len().try_into()?,
+        }
+        .write(&mut body)?;
+        body.write_all(meta.as_bytes())?;
+
+        let body = body.into_inner();
+        let mut header = Cursor::new(vec![]);
+        MoofHeader {
+            crc: crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC).checksum(&body),
+        }
+        .write(&mut header)?;
+        w.write_all(header.get_ref())?;
+        w.write_all(&body)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macformat::{MacFormatDecoder, MacFormatEncoder};
+    use crate::TrackType;
+
+    #[test]
+    fn save_gcr() {
+        let data = (0..FloppyType::Mac800K.get_logical_size())
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let img = MacFormatEncoder::encode(FloppyType::Mac800K, &data, None, "gcr").unwrap();
+        let loaded = Moof::load(&Moof::save_vec(&img).unwrap(), None).unwrap();
+
+        assert_eq!(loaded.get_type(), FloppyType::Mac800K);
+        assert_eq!(loaded.get_title(), "gcr");
+        for track in 0..img.get_track_count() {
+            for side in 0..img.get_side_count() {
+                let TrackLength::Bits(len) = img.get_track_length(side, track) else {
+                    panic!("Not a bitstream track");
+                };
+                assert_eq!(loaded.get_track_length(side, track), TrackLength::Bits(len));
+                assert!((0..len).all(|pos| loaded.get_track_bit(side, track, pos)
+                    == img.get_track_bit(side, track, pos)));
+            }
+        }
+        assert_eq!(MacFormatDecoder::decode(&loaded).unwrap().0, data);
+    }
+
+    #[test]
+    fn save_flux() {
+        let mut img = FloppyImage::new_empty(FloppyType::Mac400K, "flux");
+        for track in 0..img.get_track_count() {
+            // Includes transitions that need to be split up in the file
+            for t in [16, 32, 48, 300, 510] {
+                img.push_flux(0, track, t + track as i16);
+            }
+        }
+        let loaded = Moof::load(&Moof::save_vec(&img).unwrap(), None).unwrap();
+
+        assert_eq!(loaded.get_type(), FloppyType::Mac400K);
+        for track in 0..img.get_track_count() {
+            assert_eq!(loaded.get_track_type(0, track), TrackType::Flux);
+            assert_eq!(
+                loaded.get_original_track_type(0, track),
+                OriginalTrackType::Flux
+            );
+            assert_eq!(
+                loaded.flux_trackdata[0][track],
+                img.flux_trackdata[0][track]
+            );
+        }
+        assert!(loaded.get_write_protect());
+    }
+}
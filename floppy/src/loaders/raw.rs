@@ -1,13 +1,15 @@
 //! Raw, sector-based image format
 
-use super::FloppyImageLoader;
+use super::{FloppyImageLoader, FloppyImageSaver};
+use crate::macformat::{MacFormatDecoder, MacFormatEncoder};
+use crate::mfmformat::MfmFormatEncoder;
 use crate::FloppyType;
-use crate::{macformat::MacFormatEncoder, FloppyImage};
+use crate::{Floppy, FloppyImage};
 
 use anyhow::{bail, Result};
 use strum::IntoEnumIterator;
 
-/// Raw image loader
+/// Raw image loader/saver
 pub struct RawImage {}
 
 impl FloppyImageLoader for RawImage {
@@ -17,6 +19,46 @@ impl FloppyImageLoader for RawImage {
             bail!("Invalid raw image length: {}", data.len())
         };
 
-        MacFormatEncoder::encode(floppytype, data, None, filename.unwrap_or_default())
+        let title = filename.unwrap_or_default();
+        if floppytype.is_mfm() {
+            MfmFormatEncoder::encode(floppytype, data, title)
+        } else {
+            MacFormatEncoder::encode(floppytype, data, None, title)
+        }
+    }
+}
+
+impl FloppyImageSaver for RawImage {
+    fn write(img: &FloppyImage, w: &mut impl std::io::Write) -> Result<()> {
+        if img.get_type().is_mfm() {
+            bail!(
+                "Saving {} disks as raw image is not supported",
+                img.get_type()
+            );
+        }
+        let (data, _) = MacFormatDecoder::decode(img)?;
+        w.write_all(&data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_gcr() {
+        let data = (0..FloppyType::Mac400K.get_logical_size())
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let img = RawImage::load(&data, None).unwrap();
+        assert_eq!(RawImage::save_vec(&img).unwrap(), data);
+    }
+
+    #[test]
+    fn save_mfm() {
+        let data = vec![0; FloppyType::Mfm720K.get_logical_size()];
+        let img = RawImage::load(&data, None).unwrap();
+        assert!(RawImage::save_vec(&img).is_err());
     }
 }
@@ -0,0 +1,198 @@
+//! 2IMG (.2mg) universal disk image format
+//! Container around sector-based image data
+//! https://apple2.org.za/gswv/a2zine/Docs/DiskImage_2MG_Info.txt
+
+use super::{Autodetect, Diskcopy42, FloppyImageLoader, ImageType};
+use crate::macformat::MacFormatEncoder;
+use crate::mfmformat::MfmFormatEncoder;
+use crate::{FloppyImage, FloppyType};
+
+use anyhow::{bail, Context, Result};
+use binrw::io::Cursor;
+use binrw::{binrw, BinRead};
+use log::*;
+use strum::IntoEnumIterator;
+
+/// Image data format
+#[binrw]
+#[derive(Debug, Clone, Copy)]
+enum TwoImgFormat {
+    /// DOS 3.3 sector order (Apple II 5.25")
+    #[brw(magic = 0u32)]
+    Dos,
+    /// ProDOS block order, which is plain logical block order
+    #[brw(magic = 1u32)]
+    ProDos,
+    /// Nibblized (Apple II 5.25")
+    #[brw(magic = 2u32)]
+    Nib,
+}
+
+/// 2IMG file header
+#[binrw]
+#[brw(little, magic = b"2IMG")]
+#[derive(Debug)]
+struct TwoImgHeader {
+    /// Creator application
+    pub creator: [u8; 4],
+    pub header_size: u16,
+    pub version: u16,
+    pub format: TwoImgFormat,
+    /// Flags, bit 31 = locked
+    pub flags: u32,
+    /// Amount of 512-byte blocks (ProDOS order only)
+    pub blocks: u32,
+    pub data_offset: u32,
+    pub data_len: u32,
+    pub comment_offset: u32,
+    pub comment_len: u32,
+}
+
+impl TwoImgHeader {
+    const FLAG_LOCKED: u32 = 1 << 31;
+
+    pub fn is_locked(&self) -> bool {
+        self.flags & Self::FLAG_LOCKED != 0
+    }
+}
+
+/// 2IMG image loader
+pub struct TwoImg {}
+
+impl FloppyImageLoader for TwoImg {
+    fn load(data: &[u8], filename: Option<&str>) -> Result<FloppyImage> {
+        let mut cursor = Cursor::new(data);
+        let header = TwoImgHeader::read(&mut cursor)?;
+
+        let payload = data
+            .get(header.data_offset as usize..)
+            .and_then(|d| d.get(..header.data_len as usize))
+            .context("Image data out of bounds")?;
+
+        let comment = data
+            .get(header.comment_offset as usize..)
+            .and_then(|d| d.get(..header.comment_len as usize))
+            .map(|c| String::from_utf8_lossy(c).trim().to_string())
+            .unwrap_or_default();
+        let title = if comment.is_empty() {
+            filename.unwrap_or_default()
+        } else {
+            &comment
+        };
+
+        let mut img = match header.format {
+            TwoImgFormat::ProDos => {
+                if Autodetect::detect(payload).ok() == Some(ImageType::DC42) {
+                    // Wrapped DiskCopy 4.2 image
+                    Diskcopy42::load(payload, filename)?
+                } else {
+                    let Some(floppytype) =
+                        FloppyType::iter().find(|t| t.get_logical_size() == payload.len())
+                    else {
+                        bail!(
+                            "Unsupported 2IMG volume size: {} bytes ({} blocks)",
+                            payload.len(),
+                            header.blocks
+                        )
+                    };
+                    if floppytype.is_mfm() {
+                        MfmFormatEncoder::encode(floppytype, payload, title)?
+                    } else {
+                        MacFormatEncoder::encode(floppytype, payload, None, title)?
+                    }
+                }
+            }
+            format => bail!("Unsupported 2IMG data format: {:?}", format),
+        };
+
+        if header.is_locked() {
+            debug!("2IMG image is locked");
+            img.set_force_wp(true);
+        }
+        Ok(img)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::FloppyImageSaver;
+    use crate::macformat::MacFormatDecoder;
+    use crate::Floppy;
+
+    /// Builds a 2IMG file in ProDOS order around `payload`
+    fn twoimg(payload: &[u8], flags: u32, comment: &str) -> Vec<u8> {
+        const HEADER_SIZE: u32 = 64;
+        let data_len = payload.len() as u32;
+
+        let mut v = vec![];
+        v.extend_from_slice(b"2IMG");
+        v.extend_from_slice(b"SNOW");
+        v.extend_from_slice(&(HEADER_SIZE as u16).to_le_bytes());
+        v.extend_from_slice(&1u16.to_le_bytes());
+        v.extend_from_slice(&1u32.to_le_bytes());
+        v.extend_from_slice(&flags.to_le_bytes());
+        v.extend_from_slice(&(data_len / 512).to_le_bytes());
+        v.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+        v.extend_from_slice(&data_len.to_le_bytes());
+        v.extend_from_slice(&(HEADER_SIZE + data_len).to_le_bytes());
+        v.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        v.resize(HEADER_SIZE as usize, 0);
+        v.extend_from_slice(payload);
+        v.extend_from_slice(comment.as_bytes());
+        v
+    }
+
+    fn data(floppytype: FloppyType) -> Vec<u8> {
+        (0..floppytype.get_logical_size())
+            .map(|i| (i % 251) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn prodos_order() {
+        let data = data(FloppyType::Mac400K);
+        let img = TwoImg::load(&twoimg(&data, 0, "Test disk"), Some("test.2mg")).unwrap();
+
+        assert_eq!(img.get_type(), FloppyType::Mac400K);
+        assert_eq!(img.get_title(), "Test disk");
+        assert!(!img.get_write_protect());
+        assert_eq!(MacFormatDecoder::decode(&img).unwrap().0, data);
+    }
+
+    #[test]
+    fn diskcopy42_payload() {
+        let data = data(FloppyType::Mac800K);
+        let dc42 = Diskcopy42::save_vec(
+            &MacFormatEncoder::encode(FloppyType::Mac800K, &data, None, "dc42").unwrap(),
+        )
+        .unwrap();
+        let img = TwoImg::load(&twoimg(&dc42, 0, ""), Some("test.2mg")).unwrap();
+
+        assert_eq!(img.get_type(), FloppyType::Mac800K);
+        assert_eq!(MacFormatDecoder::decode(&img).unwrap().0, data);
+    }
+
+    #[test]
+    fn locked() {
+        let data = data(FloppyType::Mac400K);
+        let img = TwoImg::load(&twoimg(&data, TwoImgHeader::FLAG_LOCKED, ""), None).unwrap();
+
+        assert!(img.get_write_protect());
+    }
+
+    #[test]
+    fn bad_data_size() {
+        // Not a supported volume size
+        assert!(TwoImg::load(&twoimg(&[0; 1024], 0, ""), None).is_err());
+    }
+
+    #[test]
+    fn bad_data_offset() {
+        let mut file = twoimg(&data(FloppyType::Mac400K), 0, "");
+        // Data offset beyond the end of the file
+        let len = file.len() as u32;
+        file[24..28].copy_from_slice(&len.to_le_bytes());
+        assert!(TwoImg::load(&file, None).is_err());
+    }
+}
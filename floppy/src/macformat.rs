@@ -1,6 +1,6 @@
 use anyhow::{bail, Result};
 
-use crate::{Floppy, FloppyImage, FloppyType, OriginalTrackType};
+use crate::{Floppy, FloppyImage, FloppyType, OriginalTrackType, TrackLength};
 
 pub struct MacFormatEncoder<'a> {
     data: &'a [u8],
@@ -25,6 +25,18 @@ impl<'a> MacFormatEncoder<'a> {
         0xED, 0xEE, 0xEF, 0xF2, 0xF3, 0xF4, 0xf5, 0xF6, 0xF7, 0xF9, 0xFA, 0xFB, 0xFC, 0xFD, 0xFE, 0xFF,
     ];
 
+    /// Decoding table for on-disk bytes to 6 logical bits, the inverse of
+    /// `GCR_ENCTABLE`. Invalid bytes are 0xFF.
+    const GCR_DECTABLE: [u8; 256] = {
+        let mut table = [0xFF; 256];
+        let mut i = 0;
+        while i < Self::GCR_ENCTABLE.len() {
+            table[Self::GCR_ENCTABLE[i] as usize] = i as u8;
+            i += 1;
+        }
+        table
+    };
+
     /// Sectors/track per speed group
     const SECTORS_PER_TRACK: [usize; 5] = [12, 11, 10, 9, 8];
 
@@ -156,7 +168,7 @@ impl<'a> MacFormatEncoder<'a> {
         out
     }
 
-    fn push_sector_header(&mut self, side: usize, track: usize, tsector: usize) {
+    fn push_sector_header(&mut self, side: usize, track: usize, tsector: usize) -> Result<()> {
         let mut checksum = 0u8;
         // Auto sync groups
         for _ in 0..6 {
@@ -181,7 +193,9 @@ impl<'a> MacFormatEncoder<'a> {
         let format = match self.image.floppy_type {
             FloppyType::Mac400K => 0x02,
             FloppyType::Mac800K => 0x22,
-            FloppyType::Mfm144M => todo!(),
+            FloppyType::Mfm720K | FloppyType::Mfm144M => {
+                bail!("No GCR sector format for {}", self.image.floppy_type)
+            }
         };
         self.push_physical_enc(&[format]);
         checksum ^= format;
@@ -191,6 +205,7 @@ impl<'a> MacFormatEncoder<'a> {
 
         // Bit slip sequence
         self.push_physical(Self::BIT_SLIP_SEQ);
+        Ok(())
     }
 
     fn push_sector_data(&mut self, id: u8, tag: &[u8], data: &[u8]) {
@@ -245,7 +260,7 @@ impl<'a> MacFormatEncoder<'a> {
                     } else {
                         &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
                     };
-                    self.push_sector_header(side, track, tsector);
+                    self.push_sector_header(side, track, tsector)?;
                     self.push_sector_data(tsector as u8, tag, data);
                 }
                 sector_offset += Self::SECTORS_PER_TRACK[speedgroup];
@@ -292,3 +307,232 @@ impl<'a> MacFormatEncoder<'a> {
         })
     }
 }
+
+/// Decodes the sectors of a GCR, CLV Macintosh format bitstream back to logical data
+pub struct MacFormatDecoder<'a> {
+    image: &'a FloppyImage,
+}
+
+impl<'a> MacFormatDecoder<'a> {
+    /// Amount of GCR encoded bytes in the data field of a sector, including checksum
+    const SECTOR_DATA_ENC_SIZE: usize = 703;
+
+    /// Amount of bytes to search for the data mark after a sector header
+    const DATA_MARK_WINDOW: usize = 64;
+
+    /// Reads the bytes from a bitstream track, as the disk controller would:
+    /// bits are shifted in until the MSB is set, which completes a byte.
+    ///
+    /// The track is read twice over, so sectors crossing the index are complete.
+    fn read_track(&self, side: usize, track: usize, len: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(len * 2 / 8);
+        let mut shifter = 0u8;
+
+        for pos in 0..(len * 2) {
+            shifter = (shifter << 1) | u8::from(self.image.get_track_bit(side, track, pos % len));
+            if shifter & 0x80 != 0 {
+                out.push(shifter);
+                shifter = 0;
+            }
+        }
+        out
+    }
+
+    /// Decodes GCR encoded bytes to 6-bit values. Returns None on invalid bytes.
+    fn gcr_decode(data: &[u8]) -> Option<Vec<u8>> {
+        data.iter()
+            .map(|&b| match MacFormatEncoder::GCR_DECTABLE[b as usize] {
+                0xFF => None,
+                v => Some(v),
+            })
+            .collect()
+    }
+
+    /// Decodes pre-GCR encoded sector data to tag + data and verifies the checksum.
+    /// This is the inverse of `MacFormatEncoder::encode_sector_data`.
+    fn decode_sector_data(data: &[u8]) -> Option<Vec<u8>> {
+        const LOOKUP_LEN: usize = MacFormatEncoder::SECTOR_SIZE / 3;
+
+        let mut b1 = [0u8; LOOKUP_LEN + 1];
+        let mut b2 = [0u8; LOOKUP_LEN + 1];
+        let mut b3 = [0u8; LOOKUP_LEN + 1];
+        let mut din = data.iter();
+        for i in 0..=LOOKUP_LEN {
+            let w4 = *din.next()?;
+            b1[i] = *din.next()? | ((w4 << 2) & 0xC0);
+            b2[i] = *din.next()? | ((w4 << 4) & 0xC0);
+            if i != LOOKUP_LEN {
+                b3[i] = *din.next()? | ((w4 << 6) & 0xC0);
+            }
+        }
+
+        let mut c1: u32 = 0;
+        let mut c2: u32 = 0;
+        let mut c3: u32 = 0;
+        let mut out = Vec::with_capacity(MacFormatEncoder::SECTOR_SIZE);
+        for j in 0..=LOOKUP_LEN {
+            c1 = (c1 & 0xff) << 1;
+            if (c1 & 0x0100) != 0 {
+                c1 += 1;
+            }
+
+            let carry = c1 >> 8;
+            c1 &= 0xff;
+            let val = b1[j] ^ (c1 as u8);
+            c3 += u32::from(val) + carry;
+            out.push(val);
+
+            let carry = c3 >> 8;
+            c3 &= 0xff;
+            let val = b2[j] ^ (c3 as u8);
+            c2 += u32::from(val) + carry;
+            out.push(val);
+
+            if out.len() == MacFormatEncoder::SECTOR_SIZE {
+                break;
+            }
+
+            let carry = c2 >> 8;
+            c2 &= 0xff;
+            let val = b3[j] ^ (c2 as u8);
+            c1 += u32::from(val) + carry;
+            out.push(val);
+        }
+
+        // Verify GCR checksum
+        let c4 = ((c1 & 0xc0) >> 6) | ((c2 & 0xc0) >> 4) | ((c3 & 0xc0) >> 2);
+        let checksum = [c4, c3, c2, c1].map(|c| c as u8 & 0x3f);
+        if din.as_slice() != checksum {
+            return None;
+        }
+        Some(out)
+    }
+
+    /// Decodes all sectors on a track. Returns (sector number, tag + data) pairs.
+    fn decode_track(&self, side: usize, track: usize, len: usize) -> Vec<(usize, Vec<u8>)> {
+        let bytes = self.read_track(side, track, len);
+        let mut sectors = vec![];
+
+        let mut pos = 0;
+        while let Some(offset) = bytes[pos..]
+            .windows(MacFormatEncoder::ADDRESS_MARK.len())
+            .position(|w| w == MacFormatEncoder::ADDRESS_MARK)
+        {
+            pos += offset + MacFormatEncoder::ADDRESS_MARK.len();
+
+            // Track low, sector, side/track high, format, checksum
+            let Some(header) = bytes.get(pos..(pos + 5)).and_then(Self::gcr_decode) else {
+                continue;
+            };
+            if header[0..4].iter().fold(0, |a, b| a ^ b) != header[4]
+                || usize::from(header[0]) | (usize::from(header[2] & 1) << 6) != track
+                || usize::from(header[2] >> 5) != side
+            {
+                continue;
+            }
+            let sector = usize::from(header[1]);
+
+            // Data mark, sector number, encoded data
+            let window = &bytes[pos..bytes.len().min(pos + Self::DATA_MARK_WINDOW)];
+            let Some(data_offset) = window
+                .windows(MacFormatEncoder::DATA_MARK.len())
+                .position(|w| w == MacFormatEncoder::DATA_MARK)
+            else {
+                continue;
+            };
+            let start = pos + data_offset + MacFormatEncoder::DATA_MARK.len() + 1;
+            if let Some(data) = bytes
+                .get(start..(start + Self::SECTOR_DATA_ENC_SIZE))
+                .and_then(Self::gcr_decode)
+                .and_then(|d| Self::decode_sector_data(&d))
+            {
+                if !sectors.iter().any(|&(s, _)| s == sector) {
+                    sectors.push((sector, data));
+                }
+            }
+        }
+        sectors
+    }
+
+    /// Decodes all sectors of an image to logical data and tags
+    pub fn decode(image: &'a FloppyImage) -> Result<(Vec<u8>, Vec<u8>)> {
+        let format = image.get_type();
+        match format {
+            FloppyType::Mac400K | FloppyType::Mac800K => (),
+            _ => bail!("Unsupported disk format: {:?}", format),
+        }
+
+        let decoder = Self { image };
+        let mut data = vec![0; format.get_logical_size()];
+        let mut tags = vec![0; format.get_sector_count() * MacFormatEncoder::SECTOR_TAG_SIZE];
+        let mut sector_offset = 0usize;
+
+        for track in 0..image.get_track_count() {
+            for side in 0..image.get_side_count() {
+                let TrackLength::Bits(len) = image.get_track_length(side, track) else {
+                    bail!("Track {} side {} is a flux track", track, side);
+                };
+                let sectors = if len > 0 {
+                    decoder.decode_track(side, track, len)
+                } else {
+                    vec![]
+                };
+
+                let speedgroup = track / 16;
+                for tsector in 0..MacFormatEncoder::SECTORS_PER_TRACK[speedgroup] {
+                    let Some((_, sectordata)) = sectors.iter().find(|&&(s, _)| s == tsector) else {
+                        bail!(
+                            "Sector {} on track {} side {} not found or damaged",
+                            tsector,
+                            track,
+                            side
+                        );
+                    };
+                    let (tag, sectordata) = sectordata.split_at(MacFormatEncoder::SECTOR_TAG_SIZE);
+                    let sector = sector_offset + tsector;
+                    data[(sector * MacFormatEncoder::SECTOR_DATA_SIZE)
+                        ..((sector + 1) * MacFormatEncoder::SECTOR_DATA_SIZE)]
+                        .copy_from_slice(sectordata);
+                    tags[(sector * MacFormatEncoder::SECTOR_TAG_SIZE)
+                        ..((sector + 1) * MacFormatEncoder::SECTOR_TAG_SIZE)]
+                        .copy_from_slice(tag);
+                }
+                sector_offset += MacFormatEncoder::SECTORS_PER_TRACK[speedgroup];
+            }
+        }
+
+        Ok((data, tags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let format = FloppyType::Mac400K;
+        let data = (0..format.get_logical_size())
+            .map(|i| (i % 253) as u8)
+            .collect::<Vec<_>>();
+        let tags = (0..(format.get_sector_count() * MacFormatEncoder::SECTOR_TAG_SIZE))
+            .map(|i| (i % 7) as u8)
+            .collect::<Vec<_>>();
+        let img = MacFormatEncoder::encode(format, &data, Some(&tags), "").unwrap();
+
+        assert_eq!(MacFormatDecoder::decode(&img).unwrap(), (data, tags));
+    }
+
+    #[test]
+    fn decode_damaged() {
+        let format = FloppyType::Mac400K;
+        let data = vec![0; format.get_logical_size()];
+        let mut img = MacFormatEncoder::encode(format, &data, None, "").unwrap();
+
+        // Flip a bit in the middle of a sector's data
+        let pos = 2000 * 8;
+        let bit = img.get_track_bit(0, 5, pos);
+        img.set_track_bit(0, 5, pos, !bit);
+        assert!(MacFormatDecoder::decode(&img).is_err());
+    }
+}
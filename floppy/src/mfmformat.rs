@@ -0,0 +1,203 @@
+//! PC-style MFM sector format, as used by the SuperDrive for 720KB and 1.44MB disks
+
+use anyhow::{bail, Result};
+
+use crate::{Floppy, FloppyImage, FloppyType, OriginalTrackType};
+
+pub struct MfmFormatEncoder<'a> {
+    data: &'a [u8],
+    image: FloppyImage,
+
+    enc_track: usize,
+    enc_side: usize,
+
+    /// Last encoded data bit, to determine the next clock bit
+    last_bit: bool,
+}
+
+impl<'a> MfmFormatEncoder<'a> {
+    /// Sector data size, in bytes.
+    const SECTOR_DATA_SIZE: usize = 512;
+
+    /// Sector size code in the sector header (128 << 2 = 512 bytes)
+    const SECTOR_SIZE_CODE: u8 = 2;
+
+    /// Gap filler byte
+    const GAP_BYTE: u8 = 0x4E;
+
+    /// Gap 4a (track start), gap 1 (after index mark) and gap 2 (after sector header) lengths
+    const GAP4A_LEN: usize = 80;
+    const GAP1_LEN: usize = 50;
+    const GAP2_LEN: usize = 22;
+
+    /// Zero bytes before each mark
+    const SYNC_LEN: usize = 12;
+
+    /// 0xA1 with a dropped clock bit, precedes address and data marks
+    const SYNC_A1: u16 = 0x4489;
+
+    /// 0xC2 with a dropped clock bit, precedes the index mark
+    const SYNC_C2: u16 = 0x5224;
+
+    /// Index mark
+    const INDEX_MARK: u8 = 0xFC;
+
+    /// Address mark
+    const ADDRESS_MARK: u8 = 0xFE;
+
+    /// Data mark
+    const DATA_MARK: u8 = 0xFB;
+
+    /// Gap 3 (between sectors) length
+    const fn gap3_len(format: FloppyType) -> usize {
+        match format {
+            FloppyType::Mfm144M => 84,
+            _ => 80,
+        }
+    }
+
+    fn push_cells(&mut self, cells: u16) {
+        for b in cells.to_be_bytes() {
+            self.image.push_byte(self.enc_side, self.enc_track, b);
+        }
+    }
+
+    /// Pushes data bytes, MFM encoded
+    fn push_encoded(&mut self, data: &[u8]) {
+        for &b in data {
+            let mut cells = 0;
+            for i in (0..8).rev() {
+                let bit = b & (1 << i) != 0;
+                let clock = !self.last_bit && !bit;
+                cells = (cells << 2) | (u16::from(clock) << 1) | u16::from(bit);
+                self.last_bit = bit;
+            }
+            self.push_cells(cells);
+        }
+    }
+
+    fn push_gap(&mut self, len: usize) {
+        self.push_encoded(&vec![Self::GAP_BYTE; len]);
+    }
+
+    /// Pushes three sync marks followed by a mark byte
+    fn push_mark(&mut self, sync: u16, mark: u8) {
+        self.push_encoded(&[0; Self::SYNC_LEN]);
+        for _ in 0..3 {
+            self.push_cells(sync);
+        }
+        // Both sync marks end in a data bit of 0 (0xC2) or 1 (0xA1)
+        self.last_bit = sync & 1 != 0;
+        self.push_encoded(&[mark]);
+    }
+
+    /// Pushes an address/data mark followed by the field contents and CRC
+    fn push_field(&mut self, mark: u8, contents: &[u8]) {
+        let crc = crc::Crc::<u16>::new(&crc::CRC_16_IBM_3740);
+        let mut digest = crc.digest();
+        digest.update(&[0xA1, 0xA1, 0xA1, mark]);
+        digest.update(contents);
+
+        self.push_mark(Self::SYNC_A1, mark);
+        self.push_encoded(contents);
+        self.push_encoded(&digest.finalize().to_be_bytes());
+    }
+
+    /// Encodes logical sectors into an MFM, CAV PC format bitstream
+    pub fn encode(format: FloppyType, data: &'a [u8], name: &str) -> Result<FloppyImage> {
+        let mut encoder = Self::new(format, data, name)?;
+        encoder.run()?;
+        Ok(encoder.image)
+    }
+
+    fn run(&mut self) -> Result<()> {
+        let format = self.image.floppy_type;
+        let sectors_per_track =
+            format.get_sector_count() / self.image.get_track_count() / self.image.get_side_count();
+        let mut sector_offset = 0usize;
+
+        for track in 0..self.image.get_track_count() {
+            self.enc_track = track;
+            for side in 0..self.image.get_side_count() {
+                self.enc_side = side;
+
+                self.push_gap(Self::GAP4A_LEN);
+                self.push_mark(Self::SYNC_C2, Self::INDEX_MARK);
+                self.push_gap(Self::GAP1_LEN);
+
+                for sector in 0..sectors_per_track {
+                    let data = &self.data[((sector_offset + sector) * Self::SECTOR_DATA_SIZE)
+                        ..((sector_offset + sector + 1) * Self::SECTOR_DATA_SIZE)];
+                    self.push_field(
+                        Self::ADDRESS_MARK,
+                        &[
+                            track as u8,
+                            side as u8,
+                            (sector + 1) as u8,
+                            Self::SECTOR_SIZE_CODE,
+                        ],
+                    );
+                    self.push_gap(Self::GAP2_LEN);
+                    self.push_field(Self::DATA_MARK, data);
+                    self.push_gap(Self::gap3_len(format));
+                }
+                sector_offset += sectors_per_track;
+
+                if self.image.bitlen[side][track] > format.get_approx_track_length(track) {
+                    bail!("Track {} side {} does not fit on the disk", track, side);
+                }
+
+                // Gap 4b fills up the rest of the track
+                while self.image.bitlen[side][track] < format.get_approx_track_length(track) {
+                    self.push_gap(1);
+                }
+
+                self.image.origtracktype[side][track] = OriginalTrackType::Sector;
+            }
+        }
+        Ok(())
+    }
+
+    fn new(format: FloppyType, data: &'a [u8], title: &str) -> Result<Self> {
+        if !format.is_mfm() {
+            bail!("Unsupported disk format: {:?}", format);
+        }
+        if data.len() != format.get_logical_size() {
+            bail!(
+                "Invalid data length: {} (expected {})",
+                data.len(),
+                format.get_logical_size()
+            );
+        }
+
+        Ok(Self {
+            data,
+            image: FloppyImage::new_empty(format, title),
+            enc_track: 0,
+            enc_side: 0,
+            last_bit: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TrackLength;
+
+    #[test]
+    fn track_length() {
+        for format in [FloppyType::Mfm720K, FloppyType::Mfm144M] {
+            let data = vec![0; format.get_logical_size()];
+            let img = MfmFormatEncoder::encode(format, &data, "").unwrap();
+            for track in 0..img.get_track_count() {
+                for side in 0..img.get_side_count() {
+                    assert_eq!(
+                        img.get_track_length(side, track),
+                        TrackLength::Bits(format.get_approx_track_length(track))
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,18 @@
 use crate::keymap::map_winit_keycode;
+use crate::recent::RecentFiles;
 use crate::widgets::disassembly::Disassembly;
 use crate::widgets::framebuffer::FramebufferWidget;
 use crate::{emulator::EmulatorState, widgets::registers::RegistersWidget};
 use eframe::egui;
 use egui_file_dialog::FileDialog;
 use itertools::Itertools;
+use snow_core::emulator::comm::Watchdog;
 use snow_core::mac::video::{SCREEN_HEIGHT, SCREEN_WIDTH};
-use std::{path::Path, sync::Arc};
+use snow_core::mac::MacModel;
+use snow_core::renderer::MonochromePalette;
+use snow_floppy::loaders::Autodetect;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 pub struct SnowGui {
     wev_recv: crossbeam_channel::Receiver<egui_winit::winit::event::WindowEvent>,
@@ -18,12 +24,19 @@ pub struct SnowGui {
     floppy_dialog_driveidx: usize,
     error_dialog_open: bool,
     error_string: String,
+    exit_dialog_open: bool,
     ui_active: bool,
     last_running: bool,
 
     disassembly_open: bool,
     registers_open: bool,
 
+    /// Kiosk mode: no menus/toolbar, exit only through this shortcut
+    kiosk_quit: Option<egui::KeyboardShortcut>,
+
+    recent_roms: RecentFiles,
+    recent_floppies: RecentFiles,
+
     emu: EmulatorState,
 }
 
@@ -33,6 +46,8 @@ impl SnowGui {
         wev_recv: crossbeam_channel::Receiver<egui_winit::winit::event::WindowEvent>,
         initial_rom_file: Option<String>,
         audio_enabled: bool,
+        kiosk_quit: Option<egui::KeyboardShortcut>,
+        watchdog: Option<Watchdog>,
     ) -> Self {
         egui_material_icons::initialize(&cc.egui_ctx);
 
@@ -73,20 +88,23 @@ impl SnowGui {
             floppy_dialog_driveidx: 0,
             error_dialog_open: false,
             error_string: String::new(),
+            exit_dialog_open: false,
             ui_active: true,
             last_running: false,
 
             disassembly_open: false,
             registers_open: false,
 
-            emu: EmulatorState::new(audio_enabled),
+            kiosk_quit,
+
+            recent_roms: RecentFiles::load(cc.storage, "recent_roms"),
+            recent_floppies: RecentFiles::load(cc.storage, "recent_floppies"),
+
+            emu: EmulatorState::new(audio_enabled, watchdog),
         };
 
         if let Some(filename) = initial_rom_file {
-            match app.emu.init_from_rom(Path::new(&filename)) {
-                Ok(recv) => app.framebuffer.connect_receiver(recv),
-                Err(e) => app.show_error(&e),
-            }
+            app.load_rom(Path::new(&filename));
         }
 
         app
@@ -97,6 +115,102 @@ impl SnowGui {
         self.error_string = text.to_string();
     }
 
+    /// Loads a file dropped onto the window. ROMs start a new emulator, floppy images
+    /// are inserted into the first empty drive.
+    fn load_dropped_file(&mut self, path: &Path) {
+        let data = match std::fs::read(path) {
+            Ok(d) => d,
+            Err(e) => {
+                self.show_error(&format!("Cannot read {}: {}", path.display(), e));
+                return;
+            }
+        };
+
+        if MacModel::detect_from_rom(&data).is_some() {
+            self.load_rom(path);
+        } else if Autodetect::detect(&data).is_ok() {
+            if !self.emu.is_initialized() {
+                self.show_error(&"Load a ROM before inserting a floppy disk.");
+                return;
+            }
+            self.load_floppy_empty_drive(path);
+        } else {
+            self.show_error(&format!(
+                "{} is not a supported ROM or floppy image.",
+                path.display()
+            ));
+        }
+    }
+
+    /// Starts a new emulator with the given ROM
+    fn load_rom(&mut self, path: &Path) {
+        match self.emu.init_from_rom(path) {
+            Ok(recv) => {
+                self.framebuffer.connect_receiver(recv);
+                self.recent_roms.add(path);
+            }
+            Err(e) => self.show_error(&e),
+        }
+    }
+
+    /// Inserts a floppy image into the given drive
+    fn load_floppy(&mut self, drive: usize, path: &Path) {
+        match self.emu.load_floppy(drive, path) {
+            Ok(()) => self.recent_floppies.add(path),
+            Err(e) => self.show_error(&format!("Cannot load {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Inserts a floppy image into the first empty drive
+    fn load_floppy_empty_drive(&mut self, path: &Path) {
+        let Some(drive) = (0..3).find(|&i| self.emu.get_fdd_status(i).is_some_and(|d| d.ejected))
+        else {
+            self.show_error(&"All floppy drives already contain a disk.");
+            return;
+        };
+        self.load_floppy(drive, path);
+    }
+
+    /// Shows a list of recent files as menu items, greying out files that no longer
+    /// exist. Returns the file that was clicked, if any.
+    fn recent_files_menu(ui: &mut egui::Ui, recent: &mut RecentFiles) -> Option<PathBuf> {
+        let mut clicked = None;
+        for path in recent.iter() {
+            let name = path.file_name().map_or_else(
+                || path.display().to_string(),
+                |n| n.to_string_lossy().into_owned(),
+            );
+            if ui
+                .add_enabled(path.exists(), egui::Button::new(name))
+                .on_hover_text(path.display().to_string())
+                .on_disabled_hover_text(format!("{} (not found)", path.display()))
+                .clicked()
+            {
+                clicked = Some(path.clone());
+            }
+        }
+        ui.separator();
+        if ui.button("Remove missing files").clicked() {
+            recent.remove_missing();
+        }
+        clicked
+    }
+
+    /// Exits, asking for confirmation first if hard drive changes would be written or lost
+    fn request_exit(&mut self) {
+        if self.emu.has_dirty_disks() || self.emu.has_overlays() {
+            self.exit_dialog_open = true;
+        } else {
+            self.exit();
+        }
+    }
+
+    /// Shuts down the emulator, flushing pending disk writes, and exits
+    fn exit(&mut self) -> ! {
+        self.emu.shutdown();
+        std::process::exit(0);
+    }
+
     fn poll_winit_events(&self) {
         if self.wev_recv.is_empty() {
             return;
@@ -152,6 +266,206 @@ impl SnowGui {
         }
     }
 
+    /// Draws the menubar, hidden in kiosk mode
+    fn menubar(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("Emulator", |ui| {
+                if ui.button("Exit").clicked() {
+                    self.request_exit();
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button("Machine", |ui| {
+                if ui.button("Load ROM").clicked() {
+                    self.rom_dialog.pick_file();
+                    ui.close_menu();
+                }
+                if !self.recent_roms.is_empty() {
+                    ui.menu_button("Recent ROMs", |ui| {
+                        if let Some(path) = Self::recent_files_menu(ui, &mut self.recent_roms) {
+                            self.load_rom(&path);
+                            ui.close_menu();
+                        }
+                    });
+                }
+                if self.emu.is_initialized() {
+                    ui.separator();
+
+                    if self.emu.is_running() && ui.button("Stop").clicked() {
+                        self.emu.stop();
+                        ui.close_menu();
+                    } else if !self.emu.is_running() && ui.button("Run").clicked() {
+                        self.emu.run();
+                        ui.close_menu();
+                    }
+                    if ui.button("Single step").clicked() {
+                        self.emu.step();
+                        ui.close_menu();
+                    }
+                }
+            });
+            if self.emu.is_initialized() {
+                ui.menu_button("Drives", |ui| {
+                    for (i, d) in (0..3).filter_map(|i| self.emu.get_fdd_status(i).map(|d| (i, d)))
+                    {
+                        if ui
+                            .button(format!(
+                                "Floppy #{}: {}",
+                                i + 1,
+                                if d.ejected {
+                                    "(ejected)"
+                                } else {
+                                    &d.image_title
+                                }
+                            ))
+                            .clicked()
+                        {
+                            self.floppy_dialog_driveidx = i;
+                            self.floppy_dialog.pick_file();
+                            ui.close_menu();
+                        }
+                    }
+                    if !self.recent_floppies.is_empty() {
+                        ui.menu_button("Recent floppy images", |ui| {
+                            if let Some(path) =
+                                Self::recent_files_menu(ui, &mut self.recent_floppies)
+                            {
+                                self.load_floppy_empty_drive(&path);
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                    if let Some(hdds) = self.emu.get_hdds() {
+                        ui.separator();
+                        for (i, hdd) in hdds.iter().enumerate() {
+                            if ui
+                                .button(format!(
+                                    "SCSI #{}: {}",
+                                    i,
+                                    if let Some(hdd) = hdd {
+                                        format!(
+                                            "{:0.2}MB{}",
+                                            hdd.capacity / 1024 / 1024,
+                                            if hdd.writeprotect { " (locked)" } else { "" }
+                                        )
+                                    } else {
+                                        "(no disk)".to_string()
+                                    }
+                                ))
+                                .clicked()
+                            {
+                                ui.close_menu();
+                            }
+                        }
+                        if ui.button("Write hard drive changes to disk").clicked() {
+                            self.emu.flush_disks();
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }
+            ui.menu_button("View", |ui| {
+                ui.add(
+                    egui::Slider::new(&mut self.framebuffer.scale, 0.5..=4.0).text("Display scale"),
+                );
+                ui.menu_button("Display colors", |ui| {
+                    for palette in [
+                        MonochromePalette::Normal,
+                        MonochromePalette::Inverted,
+                        MonochromePalette::Green,
+                        MonochromePalette::Amber,
+                    ] {
+                        if ui
+                            .radio(self.emu.get_palette() == palette, palette.to_string())
+                            .clicked()
+                        {
+                            self.emu.set_palette(palette);
+                            ui.close_menu();
+                        }
+                    }
+                });
+                ui.separator();
+
+                if ui.button("Disassembly").clicked() {
+                    self.disassembly_open = !self.disassembly_open;
+                    ui.close_menu();
+                }
+                if ui.button("Registers").clicked() {
+                    self.registers_open = !self.registers_open;
+                    ui.close_menu();
+                }
+            });
+        });
+    }
+
+    /// Draws the toolbar, hidden in kiosk mode
+    fn toolbar(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.style_mut().text_styles.insert(
+                egui::TextStyle::Button,
+                egui::FontId::new(24.0, eframe::epaint::FontFamily::Proportional),
+            );
+
+            if ui
+                .add(egui::Button::new(egui_material_icons::icons::ICON_MEMORY))
+                .clicked()
+            {
+                self.rom_dialog.pick_file();
+            }
+            if self.emu.is_initialized() {
+                ui.separator();
+
+                if self.emu.is_running() {
+                    if ui
+                        .add(egui::Button::new(egui_material_icons::icons::ICON_PAUSE))
+                        .clicked()
+                    {
+                        self.emu.stop();
+                    }
+                    if ui
+                        .add(
+                            egui::Button::new(egui_material_icons::icons::ICON_FAST_FORWARD)
+                                .selected(self.emu.is_fastforward()),
+                        )
+                        .clicked()
+                    {
+                        self.emu.toggle_fastforward();
+                    }
+                    if ui
+                        .add(
+                            egui::Button::new(if self.emu.is_muted() {
+                                egui_material_icons::icons::ICON_VOLUME_OFF
+                            } else {
+                                egui_material_icons::icons::ICON_VOLUME_UP
+                            })
+                            .selected(self.emu.is_muted()),
+                        )
+                        .clicked()
+                    {
+                        self.emu.toggle_mute();
+                    }
+                } else if !self.emu.is_running() {
+                    if ui
+                        .add(egui::Button::new(
+                            egui_material_icons::icons::ICON_PLAY_ARROW,
+                        ))
+                        .clicked()
+                    {
+                        self.emu.run();
+                    }
+                    if ui
+                        .add(egui::Button::new(egui_material_icons::icons::ICON_STEP))
+                        .clicked()
+                    {
+                        self.emu.step();
+                    }
+                }
+            }
+        });
+        ui.separator();
+    }
+
     fn update_titlebar(&self, ctx: &egui::Context) {
         ctx.send_viewport_cmd(egui::ViewportCommand::Title(
             if let Some(m) = self.emu.get_model() {
@@ -172,7 +486,7 @@ impl SnowGui {
 }
 
 impl eframe::App for SnowGui {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         self.poll_winit_events();
         if self.emu.poll() {
             // Change in emulator state
@@ -181,6 +495,40 @@ impl eframe::App for SnowGui {
                 self.update_titlebar(ctx);
             }
             self.registers.update_regs(self.emu.get_regs().clone());
+            if let Some(drive) = self.emu.take_floppy_locked() {
+                self.show_error(&format!("The disk in drive #{} is locked.", drive + 1));
+            }
+            if self.emu.take_watchdog_triggered() {
+                self.show_error(&"The emulated machine appears to be hung.");
+            }
+        }
+
+        if let Some(shortcut) = self.kiosk_quit {
+            if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                self.exit();
+            }
+        }
+
+        // Closing the window goes through the same exit path as the menu, so pending
+        // hard drive changes are handled. In kiosk mode, it is ignored entirely (e.g. Alt+F4).
+        if ctx.input(|i| i.viewport().close_requested()) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            if self.kiosk_quit.is_none() {
+                self.request_exit();
+            }
+        }
+
+        // Files dropped onto the window, ignored in kiosk mode
+        if self.kiosk_quit.is_none() {
+            for path in ctx.input(|i| {
+                i.raw
+                    .dropped_files
+                    .iter()
+                    .filter_map(|f| f.path.clone())
+                    .collect::<Vec<_>>()
+            }) {
+                self.load_dropped_file(&path);
+            }
         }
 
         self.ui_active = true;
@@ -204,20 +552,59 @@ impl eframe::App for SnowGui {
         self.error_dialog_open &= error_open;
         self.ui_active &= !self.error_dialog_open;
 
+        // Exit confirmation modal, shown when hard drive changes are pending or overlays
+        // are active
+        let mut exit_open = self.exit_dialog_open;
+        egui::Window::new("Exit")
+            .open(&mut exit_open)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                let dirty = self.emu.has_dirty_disks();
+                if dirty {
+                    ui.horizontal(|ui| {
+                        ui.label(egui_material_icons::icons::ICON_WARNING);
+                        ui.label(
+                            "Hard drive changes have not been written to the disk images yet.",
+                        );
+                    });
+                }
+                if self.emu.has_overlays() {
+                    ui.horizontal(|ui| {
+                        ui.label(egui_material_icons::icons::ICON_WARNING);
+                        ui.label("Changes kept in hard drive overlays will be discarded.");
+                    });
+                }
+                ui.vertical_centered(|ui| {
+                    if ui
+                        .button(if dirty {
+                            "Write changes and exit"
+                        } else {
+                            "Exit"
+                        })
+                        .clicked()
+                    {
+                        self.exit();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.exit_dialog_open = false;
+                    }
+                });
+            });
+        self.exit_dialog_open &= exit_open;
+        self.ui_active &= !self.exit_dialog_open;
+
         // ROM picker dialog
         self.rom_dialog.update(ctx);
         if let Some(path) = self.rom_dialog.take_picked() {
-            match self.emu.init_from_rom(&path) {
-                Ok(recv) => self.framebuffer.connect_receiver(recv),
-                Err(e) => self.show_error(&e),
-            }
+            self.load_rom(&path);
         }
         self.ui_active &= self.rom_dialog.state() != egui_file_dialog::DialogState::Open;
 
         // Floppy image picker dialog
         self.floppy_dialog.update(ctx);
         if let Some(path) = self.floppy_dialog.take_picked() {
-            self.emu.load_floppy(self.floppy_dialog_driveidx, &path);
+            self.load_floppy(self.floppy_dialog_driveidx, &path);
         }
         self.ui_active &= self.floppy_dialog.state() != egui_file_dialog::DialogState::Open;
 
@@ -226,149 +613,20 @@ impl eframe::App for SnowGui {
                 ui.disable();
             }
 
-            // Menubar
-            egui::menu::bar(ui, |ui| {
-                ui.menu_button("Emulator", |ui| {
-                    if ui.button("Exit").clicked() {
-                        std::process::exit(0);
-                    }
-                });
-                ui.menu_button("Machine", |ui| {
-                    if ui.button("Load ROM").clicked() {
-                        self.rom_dialog.pick_file();
-                        ui.close_menu();
-                    }
-                    if self.emu.is_initialized() {
-                        ui.separator();
-
-                        if self.emu.is_running() && ui.button("Stop").clicked() {
-                            self.emu.stop();
-                            ui.close_menu();
-                        } else if !self.emu.is_running() && ui.button("Run").clicked() {
-                            self.emu.run();
-                            ui.close_menu();
-                        }
-                        if ui.button("Single step").clicked() {
-                            self.emu.step();
-                            ui.close_menu();
-                        }
-                    }
-                });
-                if self.emu.is_initialized() {
-                    ui.menu_button("Drives", |ui| {
-                        for (i, d) in
-                            (0..3).filter_map(|i| self.emu.get_fdd_status(i).map(|d| (i, d)))
-                        {
-                            if ui
-                                .button(format!(
-                                    "Floppy #{}: {}",
-                                    i + 1,
-                                    if d.ejected {
-                                        "(ejected)"
-                                    } else {
-                                        &d.image_title
-                                    }
-                                ))
-                                .clicked()
-                            {
-                                self.floppy_dialog_driveidx = i;
-                                self.floppy_dialog.pick_file();
-                                ui.close_menu();
-                            }
-                        }
-                        if let Some(hdd) = self.emu.get_hdds() {
-                            ui.separator();
-                            for (i, sz) in hdd.iter().enumerate() {
-                                if ui
-                                    .button(format!(
-                                        "SCSI #{}: {}",
-                                        i,
-                                        if let Some(sz) = sz {
-                                            format!("{:0.2}MB", sz / 1024 / 1024)
-                                        } else {
-                                            "(no disk)".to_string()
-                                        }
-                                    ))
-                                    .clicked()
-                                {
-                                    ui.close_menu();
-                                }
-                            }
-                        }
-                    });
-                }
-                ui.menu_button("View", |ui| {
-                    ui.add(
-                        egui::Slider::new(&mut self.framebuffer.scale, 0.5..=4.0)
-                            .text("Display scale"),
-                    );
-                    ui.separator();
-
-                    if ui.button("Disassembly").clicked() {
-                        self.disassembly_open = !self.disassembly_open;
-                        ui.close_menu();
-                    }
-                    if ui.button("Registers").clicked() {
-                        self.registers_open = !self.registers_open;
-                        ui.close_menu();
-                    }
-                });
-            });
-
-            // Toolbar
-            ui.separator();
-            ui.horizontal(|ui| {
-                ui.style_mut().text_styles.insert(
-                    egui::TextStyle::Button,
-                    egui::FontId::new(24.0, eframe::epaint::FontFamily::Proportional),
-                );
-
-                if ui
-                    .add(egui::Button::new(egui_material_icons::icons::ICON_MEMORY))
-                    .clicked()
-                {
-                    self.rom_dialog.pick_file();
-                }
-                if self.emu.is_initialized() {
-                    ui.separator();
-
-                    if self.emu.is_running() {
-                        if ui
-                            .add(egui::Button::new(egui_material_icons::icons::ICON_PAUSE))
-                            .clicked()
-                        {
-                            self.emu.stop();
-                        }
-                        if ui
-                            .add(
-                                egui::Button::new(egui_material_icons::icons::ICON_FAST_FORWARD)
-                                    .selected(self.emu.is_fastforward()),
-                            )
-                            .clicked()
-                        {
-                            self.emu.toggle_fastforward();
-                        }
-                    } else if !self.emu.is_running() {
-                        if ui
-                            .add(egui::Button::new(
-                                egui_material_icons::icons::ICON_PLAY_ARROW,
-                            ))
-                            .clicked()
-                        {
-                            self.emu.run();
-                        }
-                        if ui
-                            .add(egui::Button::new(egui_material_icons::icons::ICON_STEP))
-                            .clicked()
-                        {
-                            self.emu.step();
-                        }
-                    }
-                }
-            });
-            ui.separator();
+            if self.kiosk_quit.is_none() {
+                self.menubar(ui);
+                self.toolbar(ui);
+            }
 
             // Framebuffer display
+            if self.kiosk_quit.is_some() {
+                // Scale to fill the screen
+                let available = ui.available_size();
+                self.framebuffer.scale = f32::min(
+                    available.x / SCREEN_WIDTH as f32,
+                    available.y / SCREEN_HEIGHT as f32,
+                );
+            }
             ui.vertical_centered(|ui| {
                 let padding_height = (ui.available_height() - self.framebuffer.max_height()) / 2.0;
                 if padding_height > 0.0 {
@@ -411,6 +669,24 @@ impl eframe::App for SnowGui {
 
         // Re-render as soon as possible to keep the display updating
         ctx.request_repaint();
+
+        // Store recent files right away, exiting does not go through eframe
+        if self.recent_roms.is_dirty() || self.recent_floppies.is_dirty() {
+            if let Some(storage) = frame.storage_mut() {
+                self.save(storage);
+                storage.flush();
+            }
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.recent_roms.save(storage);
+        self.recent_floppies.save(storage);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // Flush pending disk writes if eframe exits by itself
+        self.emu.shutdown();
     }
 
     fn raw_input_hook(&mut self, ctx: &egui::Context, raw_input: &mut egui::RawInput) {
@@ -1,21 +1,24 @@
 //! Emulator state management
 
 use crate::audio::SDLAudioSink;
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use crossbeam_channel::Receiver;
 use eframe::egui;
 use log::*;
 use sdl2::audio::AudioDevice;
 use snow_core::bus::Address;
-use snow_core::cpu_m68k::disassembler::{Disassembler, DisassemblyEntry};
+use snow_core::cpu_m68k::disassembler::{disassemble_block, DisassemblyEntry};
 use snow_core::cpu_m68k::regs::RegisterFile;
-use snow_core::emulator::comm::{EmulatorCommand, EmulatorEvent, EmulatorSpeed, FddStatus};
+use snow_core::emulator::comm::{
+    EmulatorCommand, EmulatorEvent, EmulatorSpeed, FddStatus, HddStatus, Watchdog,
+};
 use snow_core::emulator::comm::{EmulatorCommandSender, EmulatorEventReceiver, EmulatorStatus};
 use snow_core::emulator::Emulator;
 use snow_core::keymap::Scancode;
 use snow_core::mac::MacModel;
-use snow_core::renderer::DisplayBuffer;
+use snow_core::renderer::{DisplayBuffer, MonochromePalette};
 use snow_core::tickable::Tickable;
+use snow_floppy::loaders::{Autodetect, FloppyImageLoader};
 use std::path::Path;
 use std::thread;
 use std::thread::JoinHandle;
@@ -33,12 +36,18 @@ pub struct EmulatorState {
     audio_enabled: bool,
     disasm_address: Address,
     disasm_code: DisassemblyListing,
+    floppy_locked: Option<usize>,
+    palette: MonochromePalette,
+    watchdog: Option<Watchdog>,
+    /// The watchdog detected a hang and did not reset the machine
+    watchdog_triggered: bool,
 }
 
 impl EmulatorState {
-    pub fn new(audio_enabled: bool) -> Self {
+    pub fn new(audio_enabled: bool, watchdog: Option<Watchdog>) -> Self {
         Self {
             audio_enabled,
+            watchdog,
             ..Default::default()
         }
     }
@@ -47,20 +56,14 @@ impl EmulatorState {
         let rom = std::fs::read(filename)?;
         self.init(
             &rom,
-            MacModel::detect_from_rom(&rom).ok_or_else(|| anyhow!("Unsupported ROM file"))?,
+            MacModel::detect_from_rom(&rom)
+                .ok_or_else(|| anyhow!("Unsupported ROM file: {}", MacModel::identify_rom(&rom)))?,
         )
     }
 
     pub fn init(&mut self, rom: &[u8], model: MacModel) -> Result<Receiver<DisplayBuffer>> {
         // Terminate running emulator (if any)
-        if let Some(emu_thread) = self.emuthread.take() {
-            self.cmdsender
-                .as_ref()
-                .unwrap()
-                .send(EmulatorCommand::Quit)
-                .unwrap();
-            emu_thread.join().unwrap();
-        }
+        self.shutdown();
 
         // Initialize emulator
         let (mut emulator, frame_recv) = Emulator::new(rom, model)?;
@@ -79,6 +82,12 @@ impl EmulatorState {
             let mut cb = self.audiosink.as_mut().unwrap().lock();
             cb.set_receiver(emulator.get_audio());
         }
+        if self.palette != MonochromePalette::default() {
+            cmd.send(EmulatorCommand::SetMonochromePalette(self.palette))?;
+        }
+        if self.watchdog.is_some() {
+            cmd.send(EmulatorCommand::SetWatchdog(self.watchdog))?;
+        }
         cmd.send(EmulatorCommand::Run)?;
 
         self.eventrecv = Some(emulator.create_event_recv());
@@ -98,6 +107,19 @@ impl EmulatorState {
         Ok(frame_recv)
     }
 
+    /// Terminates the emulator (if running) and waits for it to finish. Pending disk
+    /// writes are flushed by the emulator before terminating.
+    pub fn shutdown(&mut self) {
+        if let Some(emu_thread) = self.emuthread.take() {
+            self.cmdsender
+                .as_ref()
+                .unwrap()
+                .send(EmulatorCommand::Quit)
+                .unwrap();
+            emu_thread.join().unwrap();
+        }
+    }
+
     pub fn update_mouse(&self, p: egui::Pos2) {
         if !self.is_running() {
             return;
@@ -167,8 +189,15 @@ impl EmulatorState {
                 }
                 EmulatorEvent::NextCode((address, code)) => {
                     self.disasm_address = address;
-                    self.disasm_code =
-                        Vec::from_iter(Disassembler::from(&mut code.into_iter(), address));
+                    self.disasm_code = disassemble_block(&code, address);
+                }
+                EmulatorEvent::FloppyWriteProtected(drive) => {
+                    self.floppy_locked = Some(drive);
+                }
+                EmulatorEvent::SearchResults { .. } => (),
+                EmulatorEvent::GuestPowerEvent(_) => (),
+                EmulatorEvent::WatchdogTriggered => {
+                    self.watchdog_triggered = !self.watchdog.is_some_and(|w| w.reset);
                 }
             }
         }
@@ -216,7 +245,7 @@ impl EmulatorState {
     }
 
     /// Gets a reference to the active SCSI hard drive array.
-    pub fn get_hdds(&self) -> Option<&[Option<usize>]> {
+    pub fn get_hdds(&self) -> Option<&[Option<HddStatus>]> {
         let status = self.status.as_ref()?;
         if !status.model.has_scsi() {
             return None;
@@ -238,18 +267,18 @@ impl EmulatorState {
         }
     }
 
-    /// Loads a floppy image from the specified path.
-    pub fn load_floppy(&self, driveidx: usize, path: &Path) {
+    /// Loads a floppy image from the specified path and inserts it into a drive.
+    pub fn load_floppy(&self, driveidx: usize, path: &Path) -> Result<()> {
         let Some(ref sender) = self.cmdsender else {
-            return;
+            bail!("Emulator not initialized");
         };
 
-        sender
-            .send(EmulatorCommand::InsertFloppy(
-                driveidx,
-                path.to_string_lossy().to_string(),
-            ))
-            .unwrap();
+        let image = Autodetect::load_file(&path.to_string_lossy())?;
+        sender.send(EmulatorCommand::InsertFloppyImage(
+            driveidx,
+            Box::new(image),
+        ))?;
+        Ok(())
     }
 
     /// Returns `true` if emulator in fast-forward mode.
@@ -282,6 +311,72 @@ impl EmulatorState {
         }
     }
 
+    /// Takes the drive of the last failed write to a write protected disk, if any.
+    pub fn take_floppy_locked(&mut self) -> Option<usize> {
+        self.floppy_locked.take()
+    }
+
+    /// Returns `true` once after the watchdog detected a hang that was not handled by
+    /// resetting the machine.
+    pub fn take_watchdog_triggered(&mut self) -> bool {
+        std::mem::take(&mut self.watchdog_triggered)
+    }
+
+    /// Returns `true` if any hard drive has changes not yet written to its disk image.
+    pub fn has_dirty_disks(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|s| s.hdd.iter().flatten().any(|d| d.dirty))
+    }
+
+    /// Returns `true` if any hard drive has an active copy-on-write overlay.
+    pub fn has_overlays(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|s| s.hdd.iter().flatten().any(|d| d.overlay.is_some()))
+    }
+
+    /// Writes all pending hard drive changes to the disk images.
+    pub fn flush_disks(&self) {
+        let Some(ref sender) = self.cmdsender else {
+            return;
+        };
+        sender.send(EmulatorCommand::FlushDisks).unwrap();
+    }
+
+    /// Gets the colors of the display output
+    pub fn get_palette(&self) -> MonochromePalette {
+        self.palette
+    }
+
+    /// Sets the colors of the display output
+    pub fn set_palette(&mut self, palette: MonochromePalette) {
+        self.palette = palette;
+        if let Some(ref sender) = self.cmdsender {
+            sender
+                .send(EmulatorCommand::SetMonochromePalette(palette))
+                .unwrap();
+        }
+    }
+
+    /// Returns `true` if audio output is muted.
+    pub fn is_muted(&self) -> bool {
+        let Some(ref status) = self.status else {
+            return false;
+        };
+        status.muted
+    }
+
+    /// Toggles audio output mute.
+    pub fn toggle_mute(&self) {
+        let Some(ref sender) = self.cmdsender else {
+            return;
+        };
+        sender
+            .send(EmulatorCommand::SetMuted(!self.is_muted()))
+            .unwrap();
+    }
+
     /// Returns the currently emulated Macintosh model
     pub fn get_model(&self) -> Option<MacModel> {
         let status = self.status.as_ref()?;
@@ -4,12 +4,15 @@ mod app;
 mod audio;
 mod emulator;
 mod keymap;
+mod recent;
 mod widgets;
 
 use crate::app::SnowGui;
 use clap::Parser;
 use eframe::egui;
 use log::LevelFilter;
+use snow_core::emulator::comm::Watchdog;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(
@@ -23,6 +26,49 @@ struct Args {
     /// Disable audio
     #[arg(long, action)]
     no_audio: bool,
+
+    /// Start in fullscreen
+    #[arg(long, action)]
+    fullscreen: bool,
+
+    /// Kiosk mode: runs the given ROM in fullscreen, without menus or toolbar.
+    /// The window can only be closed using the quit shortcut (--kiosk-quit).
+    #[arg(long, action, requires = "rom_filename")]
+    kiosk: bool,
+
+    /// Shortcut to exit kiosk mode, e.g. ctrl+alt+q
+    #[arg(long, default_value = "ctrl+alt+q", value_parser = parse_shortcut)]
+    kiosk_quit: egui::KeyboardShortcut,
+
+    /// Report a hang when the screen does not change and the OS stops counting ticks
+    /// for this many (emulated) seconds
+    #[arg(long)]
+    watchdog: Option<u64>,
+
+    /// Reset the machine when the watchdog detects a hang
+    #[arg(long, requires = "watchdog")]
+    watchdog_reset: bool,
+}
+
+/// Parses a keyboard shortcut such as 'ctrl+alt+q'
+fn parse_shortcut(s: &str) -> Result<egui::KeyboardShortcut, String> {
+    let mut modifiers = egui::Modifiers::NONE;
+    let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+    let key = parts.pop().unwrap_or_default();
+    for modifier in parts {
+        let modifier = match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => egui::Modifiers::CTRL,
+            "alt" | "option" => egui::Modifiers::ALT,
+            "shift" => egui::Modifiers::SHIFT,
+            "cmd" | "command" => egui::Modifiers::COMMAND,
+            _ => return Err(format!("Unknown modifier: {}", modifier)),
+        };
+        modifiers = modifiers | modifier;
+    }
+    let key = egui::Key::from_name(key)
+        .or_else(|| egui::Key::from_name(&key.to_ascii_uppercase()))
+        .ok_or_else(|| format!("Unknown key: {}", key))?;
+    Ok(egui::KeyboardShortcut::new(modifiers, key))
 }
 
 fn main() -> eframe::Result {
@@ -40,7 +86,9 @@ fn main() -> eframe::Result {
     egui_winit::install_windowevent_hook(s);
 
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default(),
+        viewport: egui::ViewportBuilder::default()
+            .with_fullscreen(args.fullscreen || args.kiosk)
+            .with_drag_and_drop(true),
         ..Default::default()
     };
     eframe::run_native(
@@ -52,6 +100,11 @@ fn main() -> eframe::Result {
                 r,
                 args.rom_filename,
                 !args.no_audio,
+                args.kiosk.then_some(args.kiosk_quit),
+                args.watchdog.map(|secs| Watchdog {
+                    timeout: Duration::from_secs(secs),
+                    reset: args.watchdog_reset,
+                }),
             )))
         }),
     )
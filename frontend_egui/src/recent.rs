@@ -0,0 +1,63 @@
+//! Lists of recently opened files, persisted in the eframe storage
+
+use std::path::{Path, PathBuf};
+
+/// Maximum amount of files kept in a list
+const RECENT_MAX: usize = 10;
+
+/// Recently opened files, most recent first
+pub struct RecentFiles {
+    /// Key in the eframe storage
+    key: &'static str,
+    files: Vec<PathBuf>,
+    /// Changed since last save
+    dirty: bool,
+}
+
+impl RecentFiles {
+    /// Loads the list from storage, or creates an empty list
+    pub fn load(storage: Option<&dyn eframe::Storage>, key: &'static str) -> Self {
+        Self {
+            key,
+            files: storage
+                .and_then(|s| eframe::get_value(s, key))
+                .unwrap_or_default(),
+            dirty: false,
+        }
+    }
+
+    /// Saves the list to storage
+    pub fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, self.key, &self.files);
+        self.dirty = false;
+    }
+
+    /// Returns `true` if the list changed since it was last saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Moves a file to the top of the list
+    pub fn add(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.files.retain(|p| *p != path);
+        self.files.insert(0, path);
+        self.files.truncate(RECENT_MAX);
+        self.dirty = true;
+    }
+
+    /// Removes files that no longer exist
+    pub fn remove_missing(&mut self) {
+        let len = self.files.len();
+        self.files.retain(|p| p.exists());
+        self.dirty |= self.files.len() != len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter()
+    }
+}
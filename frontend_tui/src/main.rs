@@ -2,7 +2,7 @@ mod keymap_sdl;
 mod renderer_sdl;
 mod ui;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use keymap_sdl::map_sdl_keycode;
 use log::*;
@@ -10,18 +10,20 @@ use ratatui::crossterm::execute;
 use ratatui::crossterm::terminal::{disable_raw_mode, LeaveAlternateScreen};
 use sdl2::event::{Event, WindowEvent};
 use sdl2::mouse::MouseButton;
-use snow_core::emulator::comm::EmulatorCommand;
+use snow_core::emulator::comm::{EmulatorCommand, Watchdog};
 use snow_core::emulator::Emulator;
+use snow_core::keymap::modifier_scancode;
 use snow_core::mac::video::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use snow_core::mac::MacModel;
-use snow_core::tickable::Tickable;
+use snow_core::tickable::{Tickable, TICKS_PER_SECOND};
 use ui::UserInterface;
 
 use std::panic::{set_hook, take_hook};
+use std::time::Duration;
 use std::{fs, thread};
 
 use renderer_sdl::{SDLAudioSink, SDLEventPump, SDLRenderer};
-use snow_core::renderer::Renderer;
+use snow_core::renderer::{DisplayAdjust, MonochromePalette, Renderer};
 
 #[derive(Eq, PartialEq, Clone, Copy, clap::ValueEnum)]
 enum MouseControl {
@@ -53,6 +55,30 @@ impl From<Speed> for snow_core::emulator::comm::EmulatorSpeed {
     }
 }
 
+#[derive(Eq, PartialEq, Clone, Copy, clap::ValueEnum)]
+/// Display colors
+enum Palette {
+    /// Black on white
+    Normal,
+    /// White on black
+    Inverted,
+    /// Green phosphor
+    Green,
+    /// Amber phosphor
+    Amber,
+}
+
+impl From<Palette> for MonochromePalette {
+    fn from(value: Palette) -> Self {
+        match value {
+            Palette::Normal => Self::Normal,
+            Palette::Inverted => Self::Inverted,
+            Palette::Green => Self::Green,
+            Palette::Amber => Self::Amber,
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(
     about = "Snow - Classic Macintosh emulator",
@@ -77,6 +103,10 @@ struct Args {
     #[arg(long, value_enum, default_value_t=MouseControl::Absolute)]
     mouse: MouseControl,
 
+    /// Scaling factor for relative mouse motion
+    #[arg(long, default_value_t = 1.0)]
+    mouse_scale: f32,
+
     /// Scaling factor for the display
     #[arg(long, default_value_t = 2)]
     scale: usize,
@@ -84,6 +114,61 @@ struct Args {
     /// Emulation speed
     #[arg(long, value_enum, default_value_t=Speed::Accurate)]
     speed: Speed,
+
+    /// Display colors
+    #[arg(long, value_enum, default_value_t=Palette::Normal)]
+    palette: Palette,
+
+    /// Display brightness (-1.0 - 1.0)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    brightness: f32,
+
+    /// Display contrast
+    #[arg(long, default_value_t = 1.0)]
+    contrast: f32,
+
+    /// Display gamma
+    #[arg(long, default_value_t = 1.0)]
+    gamma: f32,
+
+    /// Play floppy drive sound effects
+    #[arg(long)]
+    drive_sounds: bool,
+
+    /// Modifier keys to hold down during boot, comma separated
+    /// (shift, option, command, control)
+    #[arg(long, value_delimiter = ',')]
+    hold_keys: Vec<String>,
+
+    /// Time to hold the boot keys for, in (emulated) seconds
+    #[arg(long, default_value_t = 10)]
+    hold_keys_time: usize,
+
+    /// Run the memory test of the ROM at boot instead of skipping it
+    #[arg(long)]
+    memtest: bool,
+
+    /// SCSI ID of the hard drive to start up from
+    #[arg(long)]
+    startup_scsi: Option<usize>,
+
+    /// SCSI IDs of hard drives to write protect, comma separated.
+    /// The disk images of these drives are not modified.
+    #[arg(long, value_delimiter = ',')]
+    hdd_read_only: Vec<usize>,
+
+    /// Report a hang when the screen does not change and the OS stops counting ticks
+    /// for this many (emulated) seconds
+    #[arg(long)]
+    watchdog: Option<u64>,
+
+    /// Reset the machine when the watchdog detects a hang
+    #[arg(long, requires = "watchdog")]
+    watchdog_reset: bool,
+
+    /// Exit when the guest shuts down the machine (e.g. 'Shut Down' in the Finder)
+    #[arg(long)]
+    quit_on_shutdown: bool,
 }
 
 /// Sets up a panic handler that restores the terminal back to the original state
@@ -118,7 +203,12 @@ fn main() -> Result<()> {
 
     // Initialize ROM
     let rom = fs::read(&args.rom_filename)?;
-    let model = MacModel::detect_from_rom(&rom).expect("Cannot detect model from ROM file");
+    let Some(model) = MacModel::detect_from_rom(&rom) else {
+        bail!(
+            "Cannot detect model from ROM file: {}",
+            MacModel::identify_rom(&rom)
+        );
+    };
 
     // Initialize emulator
     let (mut emulator, frame_recv) = Emulator::new(&rom, model)?;
@@ -126,10 +216,54 @@ fn main() -> Result<()> {
     if let Some(floppy_fn) = args.floppy_filename {
         cmd.send(EmulatorCommand::InsertFloppy(0, floppy_fn))?;
     }
+    if args.memtest {
+        cmd.send(EmulatorCommand::EnableMemtest)?;
+    }
+    if let Some(id) = args.startup_scsi {
+        cmd.send(EmulatorCommand::SetStartupDisk(Some(id)))?;
+    }
+    if let Some(secs) = args.watchdog {
+        cmd.send(EmulatorCommand::SetWatchdog(Some(Watchdog {
+            timeout: Duration::from_secs(secs),
+            reset: args.watchdog_reset,
+        })))?;
+    }
+    if args.quit_on_shutdown {
+        cmd.send(EmulatorCommand::SetPowerEvents(true))?;
+    }
     if !args.stop {
         cmd.send(EmulatorCommand::Run)?;
     }
     cmd.send(EmulatorCommand::SetSpeed(args.speed.into()))?;
+    if !args.hold_keys.is_empty() {
+        let keys = args
+            .hold_keys
+            .iter()
+            .map(|k| modifier_scancode(k).with_context(|| format!("Unknown modifier key: {}", k)))
+            .collect::<Result<Vec<_>>>()?;
+        let until = args.hold_keys_time * TICKS_PER_SECOND;
+        cmd.send(EmulatorCommand::HoldKeys(keys, until))?;
+    }
+    if args.drive_sounds {
+        cmd.send(EmulatorCommand::SetDriveSounds(true))?;
+    }
+    let adjust = DisplayAdjust {
+        brightness: args.brightness,
+        contrast: args.contrast,
+        gamma: args.gamma,
+    };
+    if adjust != DisplayAdjust::default() {
+        cmd.send(EmulatorCommand::SetDisplayAdjust(adjust))?;
+    }
+    if args.palette != Palette::Normal {
+        cmd.send(EmulatorCommand::SetMonochromePalette(args.palette.into()))?;
+    }
+    for &id in &args.hdd_read_only {
+        cmd.send(EmulatorCommand::SetHddWriteProtect(id, true))?;
+    }
+    if args.mouse_scale != 1.0 {
+        cmd.send(EmulatorCommand::SetMouseScale(args.mouse_scale))?;
+    }
 
     // Initialize audio
     let _audiodev = SDLAudioSink::new(emulator.get_audio())?;
@@ -168,9 +302,7 @@ fn main() -> Result<()> {
         // Process SDL events
         while let Some(event) = eventpump.wait(10) {
             match event {
-                Event::Quit { .. } => {
-                    break 'mainloop;
-                }
+                Event::Quit { .. } => ui.request_exit(),
                 Event::KeyDown {
                     keycode: Some(k), ..
                 } => {
@@ -4,9 +4,12 @@ mod status;
 
 use std::fs;
 use std::io::stdout;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 use anyhow::{bail, Context, Result};
 use browser::{BrowserWidget, BrowserWidgetEvent, BrowserWidgetState};
+use chrono::NaiveDateTime;
 use debugger::{DebuggerWidget, DebuggerWidgetEvent, DebuggerWidgetState};
 use log::*;
 use ratatui::buffer::Buffer;
@@ -22,12 +25,15 @@ use ratatui::text::Line;
 use ratatui::widgets::{Paragraph, Widget};
 use ratatui::Terminal;
 use snow_core::bus::Address;
-use snow_core::cpu_m68k::disassembler::{Disassembler, DisassemblyEntry};
+use snow_core::cpu_m68k::disassembler::{disassemble_block, DisassemblyEntry};
 use snow_core::cpu_m68k::regs::RegisterFile;
 use snow_core::emulator::comm::{
     EmulatorCommand, EmulatorCommandSender, EmulatorEvent, EmulatorEventReceiver, EmulatorSpeed,
-    EmulatorStatus,
+    EmulatorStatus, PatchSize, PowerEvent, Watchpoint,
 };
+use snow_core::emulator::condition::BreakCondition;
+use snow_core::renderer::MonochromePalette;
+use snow_floppy::FloppyType;
 use status::StatusWidget;
 use tui_logger::{TuiLoggerLevelOutput, TuiLoggerWidget, TuiWidgetEvent, TuiWidgetState};
 
@@ -44,6 +50,10 @@ enum View {
 pub struct UserInterface {
     cmd: Option<String>,
     exit: bool,
+    /// Exit was requested once while hard drive overlays are active
+    exit_warned: bool,
+    /// Time the guest requested to shut down
+    shutdown: Option<Instant>,
 
     view: View,
 
@@ -64,6 +74,8 @@ pub struct UserInterface {
 
 impl UserInterface {
     const DIR_FLOPPIES: &'static str = "floppies/";
+    /// Time the guest gets to finish shutting down (e.g. flushing disks) before exiting
+    const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
 
     pub fn new(
         romfn: &str,
@@ -85,6 +97,8 @@ impl UserInterface {
 
             cmd: None,
             exit: false,
+            exit_warned: false,
+            shutdown: None,
 
             view: View::Status,
             romfn: romfn.to_string(),
@@ -116,8 +130,8 @@ impl UserInterface {
         Ok(())
     }
 
-    fn generate_disassembly(&mut self, pc: Address, code: Vec<u8>) -> Result<()> {
-        self.disassembly = Vec::from_iter(Disassembler::from(&mut code.into_iter(), pc));
+    fn generate_disassembly(&mut self, pc: Address, code: &[u8]) -> Result<()> {
+        self.disassembly = disassemble_block(code, pc);
 
         Ok(())
     }
@@ -134,9 +148,41 @@ impl UserInterface {
                     self.lastregs = self.emustatus.regs.clone();
                     self.emustatus = s;
                 }
-                EmulatorEvent::NextCode((a, i)) => self.generate_disassembly(a, i)?,
+                EmulatorEvent::NextCode((a, i)) => self.generate_disassembly(a, &i)?,
+                EmulatorEvent::FloppyWriteProtected(drive) => {
+                    warn!("The disk in drive #{} is locked", drive + 1);
+                }
+                EmulatorEvent::SearchResults {
+                    addresses,
+                    truncated,
+                } => {
+                    for addr in &addresses {
+                        info!("Found: ${:06X}", addr);
+                    }
+                    if truncated {
+                        info!("(more results omitted)");
+                    }
+                }
+                EmulatorEvent::GuestPowerEvent(PowerEvent::Shutdown) => {
+                    info!("Guest is shutting down, exiting");
+                    self.shutdown = Some(Instant::now());
+                }
+                // The ROM restarts the machine by itself
+                EmulatorEvent::GuestPowerEvent(PowerEvent::Restart) => {
+                    info!("Guest is restarting");
+                }
+                EmulatorEvent::WatchdogTriggered => {
+                    warn!("The emulated machine appears to be hung");
+                }
             }
         }
+        if self
+            .shutdown
+            .is_some_and(|t| t.elapsed() >= Self::SHUTDOWN_GRACE)
+        {
+            self.shutdown = None;
+            self.request_exit();
+        }
 
         self.draw(terminal)?;
 
@@ -167,7 +213,7 @@ impl UserInterface {
 
                 match (self.view, key.code) {
                     (_, KeyCode::Char('/')) => self.cmd = Some("".to_string()),
-                    (_, KeyCode::F(10)) => return Ok(false),
+                    (_, KeyCode::F(10)) => self.request_exit(),
                     (View::Status, KeyCode::F(1)) => self.view = View::Log,
                     (_, KeyCode::F(1)) => self.view = View::Status,
                     (_, KeyCode::F(2)) => self.view = View::Debugger,
@@ -241,6 +287,27 @@ impl UserInterface {
         Ok(true)
     }
 
+    /// Exits the emulator. Changes in hard drive overlays are lost on exit, so if any
+    /// are active, the first request only warns.
+    pub fn request_exit(&mut self) {
+        let overlays = self
+            .emustatus
+            .hdd
+            .iter()
+            .flatten()
+            .filter(|d| d.overlay.is_some())
+            .count();
+        if overlays > 0 && !self.exit_warned {
+            warn!(
+                "{} hard drive overlay(s) active, changes will be lost on exit. Use /overlay <id> commit to keep them, or exit again to discard them.",
+                overlays
+            );
+            self.exit_warned = true;
+        } else {
+            self.exit = true;
+        }
+    }
+
     pub fn shutdown_terminal(_terminal: &mut Terminal<impl Backend>) -> Result<()> {
         stdout().execute(LeaveAlternateScreen)?;
         disable_raw_mode()?;
@@ -259,8 +326,51 @@ impl UserInterface {
                         .trim_start_matches("0x"),
                     16,
                 )?;
+                match tokens.get(2).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    None => self
+                        .cmdsender
+                        .send(EmulatorCommand::ToggleBreakpoint(addr))?,
+                    Some("if") => {
+                        let condition = BreakCondition::parse(&tokens[3..].join(" "))?;
+                        self.cmdsender
+                            .send(EmulatorCommand::SetConditionalBreakpoint(addr, condition))?;
+                    }
+                    _ => bail!("Syntax: b <address> [if <condition>]"),
+                }
+                Ok(())
+            }
+            "btrap" => {
+                let trap = u16::from_str_radix(
+                    tokens
+                        .get(1)
+                        .context("Need trap word")?
+                        .trim_start_matches("0x"),
+                    16,
+                )?;
+                self.cmdsender
+                    .send(EmulatorCommand::ToggleTrapBreakpoint(trap))?;
+                Ok(())
+            }
+            "watch" => {
+                let addr = Address::from_str_radix(
+                    tokens
+                        .get(1)
+                        .context("Need address")?
+                        .trim_start_matches("0x"),
+                    16,
+                )?;
+                let (on_read, on_write) = match tokens.get(2).copied().unwrap_or("rw") {
+                    "r" => (true, false),
+                    "w" => (false, true),
+                    "rw" => (true, true),
+                    m => bail!("Invalid watchpoint mode: {} (r, w or rw)", m),
+                };
                 self.cmdsender
-                    .send(EmulatorCommand::ToggleBreakpoint(addr))?;
+                    .send(EmulatorCommand::ToggleWatchpoint(Watchpoint {
+                        addr,
+                        on_read,
+                        on_write,
+                    }))?;
                 Ok(())
             }
             "loadbin" => {
@@ -327,10 +437,132 @@ impl UserInterface {
                     .send(EmulatorCommand::SaveFloppy(2, filename))?;
                 Ok(())
             }
+            "format" => {
+                let drive = tokens
+                    .get(1)
+                    .context("Need drive number")?
+                    .parse::<usize>()?;
+                if !(1..=3).contains(&drive) {
+                    bail!("Drive number must be 1 - 3");
+                }
+                let floppy_type = match tokens.get(2).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    Some("400k") => FloppyType::Mac400K,
+                    Some("800k") => FloppyType::Mac800K,
+                    Some("720k") => FloppyType::Mfm720K,
+                    Some("1440k") => FloppyType::Mfm144M,
+                    _ => bail!("Requires a disk type: 400k, 800k, 720k, 1440k"),
+                };
+                self.cmdsender
+                    .send(EmulatorCommand::FormatFloppy(drive - 1, floppy_type))?;
+                Ok(())
+            }
+            "wp" | "writeprotect" => {
+                let drive = tokens
+                    .get(1)
+                    .context("Need drive number")?
+                    .parse::<usize>()?;
+                if !(1..=3).contains(&drive) {
+                    bail!("Drive number must be 1 - 3");
+                }
+                let wp = match tokens.get(2).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => bail!("Requires an argument: on, off"),
+                };
+                self.cmdsender
+                    .send(EmulatorCommand::SetFloppyWriteProtect(drive - 1, wp))?;
+                Ok(())
+            }
+            "hddwp" => {
+                let id = tokens.get(1).context("Need SCSI ID")?.parse::<usize>()?;
+                if id > 6 {
+                    bail!("SCSI ID must be 0 - 6");
+                }
+                let wp = match tokens.get(2).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => bail!("Requires an argument: on, off"),
+                };
+                self.cmdsender
+                    .send(EmulatorCommand::SetHddWriteProtect(id, wp))?;
+                Ok(())
+            }
+            "overlay" => {
+                let id = tokens.get(1).context("Need SCSI ID")?.parse::<usize>()?;
+                if id > 6 {
+                    bail!("SCSI ID must be 0 - 6");
+                }
+                let cmd = match tokens.get(2).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    Some("start") => EmulatorCommand::StartHddOverlay(id),
+                    Some("commit") => EmulatorCommand::CommitHddOverlay(id),
+                    Some("discard") => EmulatorCommand::DiscardHddOverlay(id),
+                    _ => bail!("Requires an argument: start, commit, discard"),
+                };
+                self.cmdsender.send(cmd)?;
+                Ok(())
+            }
+            "rpm" => {
+                let drive = tokens
+                    .get(1)
+                    .context("Need drive number")?
+                    .parse::<usize>()?;
+                if !(1..=3).contains(&drive) {
+                    bail!("Drive number must be 1 - 3");
+                }
+                let adjustment = tokens
+                    .get(2)
+                    .context("Need adjustment (rounds/minute)")?
+                    .parse::<i32>()?;
+                self.cmdsender
+                    .send(EmulatorCommand::SetFloppyRpmAdjustment(
+                        drive - 1,
+                        adjustment,
+                    ))?;
+                Ok(())
+            }
+            "drivesounds" => {
+                let enable = match tokens.get(1).copied() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => bail!("Usage: /drivesounds <on|off>"),
+                };
+                self.cmdsender
+                    .send(EmulatorCommand::SetDriveSounds(enable))?;
+                Ok(())
+            }
+            "volume" => {
+                let cmd = match tokens.get(1).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    Some("mute") => EmulatorCommand::SetMuted(true),
+                    Some("unmute") => EmulatorCommand::SetMuted(false),
+                    Some(v) => {
+                        let v = v.parse::<u8>()?;
+                        if v > 100 {
+                            bail!("Volume must be 0 - 100");
+                        }
+                        EmulatorCommand::SetVolume(f32::from(v) / 100.0)
+                    }
+                    None => bail!("Usage: /volume <0 - 100|mute|unmute>"),
+                };
+                self.cmdsender.send(cmd)?;
+                Ok(())
+            }
             "trace" => {
                 self.cmdsender.send(EmulatorCommand::ToggleBusTrace)?;
                 Ok(())
             }
+            "nmi" | "interrupt" => {
+                self.cmdsender.send(EmulatorCommand::ProgKey)?;
+                Ok(())
+            }
+            "itrace" => {
+                let filename = match tokens.get(1) {
+                    None | Some(&"off") => None,
+                    Some(f) => Some(PathBuf::from(f)),
+                };
+                self.cmdsender
+                    .send(EmulatorCommand::SetInstructionTrace(filename))?;
+                Ok(())
+            }
             "setpc" => {
                 let val = u32::from_str_radix(
                     tokens
@@ -347,14 +579,120 @@ impl UserInterface {
                     Some("accurate") => EmulatorSpeed::Accurate,
                     Some("dynamic") => EmulatorSpeed::Dynamic,
                     Some("uncapped") => EmulatorSpeed::Uncapped,
-                    Some("video") => EmulatorSpeed::Video,
-                    _ => bail!("Requires an argument: accurate, dynamic, uncapped"),
+                    Some("video") => match tokens.get(2) {
+                        Some(fps) => EmulatorSpeed::VideoFps(fps.parse()?),
+                        None => EmulatorSpeed::Video,
+                    },
+                    Some("scaled") => EmulatorSpeed::Scaled(
+                        tokens.get(2).context("Need speed factor")?.parse()?,
+                    ),
+                    _ => bail!(
+                        "Requires an argument: accurate, dynamic, uncapped, video [fps], scaled <factor>"
+                    ),
                 };
                 self.cmdsender.send(EmulatorCommand::SetSpeed(speed))?;
                 Ok(())
             }
+            "patch" => {
+                let addr = Address::from_str_radix(
+                    tokens
+                        .get(1)
+                        .context("Need address")?
+                        .trim_start_matches("0x"),
+                    16,
+                )?;
+                let value = u32::from_str_radix(
+                    tokens
+                        .get(2)
+                        .context("Need value")?
+                        .trim_start_matches("0x"),
+                    16,
+                )?;
+                let size = match tokens.get(3).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    None | Some("b") => PatchSize::Byte,
+                    Some("w") => PatchSize::Word,
+                    Some("l") => PatchSize::Long,
+                    _ => bail!("Size must be one of: b, w, l"),
+                };
+                self.cmdsender
+                    .send(EmulatorCommand::AddPatch { addr, value, size })?;
+                Ok(())
+            }
+            "unpatch" => {
+                let addr = Address::from_str_radix(
+                    tokens
+                        .get(1)
+                        .context("Need address")?
+                        .trim_start_matches("0x"),
+                    16,
+                )?;
+                self.cmdsender.send(EmulatorCommand::RemovePatch(addr))?;
+                Ok(())
+            }
+            "search" | "searchnext" => {
+                // Hexadecimal bytes, '??' is a wildcard
+                let hex = tokens.get(1).context("Need pattern")?;
+                if hex.is_empty() || hex.len() % 2 != 0 || !hex.is_ascii() {
+                    bail!("Pattern must be a sequence of hexadecimal bytes");
+                }
+                let mut pattern = vec![];
+                let mut mask = vec![];
+                for i in (0..hex.len()).step_by(2) {
+                    match &hex[i..i + 2] {
+                        "??" => {
+                            pattern.push(0);
+                            mask.push(0);
+                        }
+                        b => {
+                            pattern.push(u8::from_str_radix(b, 16)?);
+                            mask.push(0xFF);
+                        }
+                    }
+                }
+                self.cmdsender.send(EmulatorCommand::SearchMemory {
+                    pattern,
+                    mask: if mask.contains(&0) { Some(mask) } else { None },
+                    narrow: tokens[0] == "searchnext",
+                })?;
+                Ok(())
+            }
+            "palette" => {
+                let palette = match tokens.get(1).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    Some("normal") => MonochromePalette::Normal,
+                    Some("inverted") => MonochromePalette::Inverted,
+                    Some("green") => MonochromePalette::Green,
+                    Some("amber") => MonochromePalette::Amber,
+                    _ => bail!("Requires a palette: normal, inverted, green, amber"),
+                };
+                self.cmdsender
+                    .send(EmulatorCommand::SetMonochromePalette(palette))?;
+                Ok(())
+            }
+            "startup" => {
+                let disk = match tokens.get(1).map(|s| s.to_ascii_lowercase()).as_deref() {
+                    Some("auto") => None,
+                    Some(id) => Some(id.parse::<usize>()?),
+                    None => bail!("Requires a SCSI ID or 'auto'"),
+                };
+                self.cmdsender.send(EmulatorCommand::SetStartupDisk(disk))?;
+                Ok(())
+            }
+            "date" => {
+                let dt = NaiveDateTime::parse_from_str(&tokens[1..].join(" "), "%Y-%m-%d %H:%M:%S")
+                    .context("Usage: /date <YYYY-MM-DD HH:MM:SS>")?;
+                self.cmdsender.send(EmulatorCommand::SetDateTime(dt))?;
+                Ok(())
+            }
+            "resetpram" => {
+                self.cmdsender.send(EmulatorCommand::ResetPram)?;
+                Ok(())
+            }
+            "flush" => {
+                self.cmdsender.send(EmulatorCommand::FlushDisks)?;
+                Ok(())
+            }
             "exit" | "quit" => {
-                self.exit = true;
+                self.request_exit();
                 Ok(())
             }
             _ => bail!("Unknown command"),
@@ -5,7 +5,8 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Block, Paragraph},
 };
-use snow_core::emulator::comm::EmulatorStatus;
+use snow_core::emulator::comm::{EmulatorSpeed, EmulatorStatus};
+use snow_core::mac::scsi::DISK_BLOCKSIZE;
 use tui_logger::{TuiLoggerLevelOutput, TuiLoggerWidget};
 
 pub struct StatusWidget<'a> {
@@ -59,15 +60,26 @@ impl Widget for StatusWidget<'_> {
                     })
                     .style(Style::default().white())
                 },
+                if !drive.ejected && drive.writeprotect {
+                    Span::from(" (locked)").style(Style::default().yellow())
+                } else {
+                    Span::from("")
+                },
             ]));
             drivep.push(Line::from(vec![
                 Span::from("     "),
                 if drive.motor && !drive.writing {
-                    Span::from(format!("Reading (track {})", drive.track))
-                        .style(Style::default().blue())
+                    Span::from(format!(
+                        "Reading (track {}, {} rpm)",
+                        drive.track, drive.rpm
+                    ))
+                    .style(Style::default().blue())
                 } else if drive.writing {
-                    Span::from(format!("Writing (track {})", drive.track))
-                        .style(Style::default().red())
+                    Span::from(format!(
+                        "Writing (track {}, {} rpm)",
+                        drive.track, drive.rpm
+                    ))
+                    .style(Style::default().red())
                 } else if drive.ejected {
                     Span::from(format!("Ejected (track {})", drive.track))
                         .style(Style::default().dark_gray())
@@ -90,14 +102,23 @@ impl Widget for StatusWidget<'_> {
 
         if self.emustatus.model.has_scsi() {
             Paragraph::new(Vec::from_iter(self.emustatus.hdd.iter().enumerate().map(
-                |(i, &d)| {
+                |(i, d)| {
                     Line::from(vec![
                         Span::from(format!(" #{} ", i)).style(Style::default().blue().bold()),
-                        if let Some(capacity) = d {
+                        if let Some(hdd) = d {
+                            let overlay = hdd
+                                .overlay
+                                .map(|b| format!(" overlay: {} blocks", b))
+                                .unwrap_or_default();
                             Span::from(format!(
-                                "hdd{}.img ({:0.1} MB)",
+                                "hdd{}.img ({:0.1} MB, {} blocks of {}){}{}{}",
                                 i,
-                                (capacity as f64) / 1024.0 / 1024.0
+                                (hdd.capacity as f64) / 1024.0 / 1024.0,
+                                hdd.capacity / DISK_BLOCKSIZE,
+                                DISK_BLOCKSIZE,
+                                if hdd.writeprotect { " locked" } else { "" },
+                                if hdd.dirty { " unsaved" } else { "" },
+                                overlay
                             ))
                         } else {
                             Span::from("not present").dark_gray()
@@ -135,9 +156,22 @@ impl Widget for StatusWidget<'_> {
             Line::from(vec![
                 Span::from(Self::ASCIIMAC[4]).white(),
                 Span::from("Speed  ").style(Style::default().blue().bold()),
-                Span::from(format!("{:>14}", self.emustatus.speed)).style(Style::default().white()),
+                Span::from(format!(
+                    "{:>14}",
+                    match self.emustatus.speed {
+                        EmulatorSpeed::VideoFps(fps) => format!("Video {}fps", fps),
+                        EmulatorSpeed::Scaled(factor) => format!("{:.2}x", factor),
+                        speed => speed.to_string(),
+                    }
+                ))
+                .style(Style::default().white()),
+            ]),
+            Line::from(vec![
+                Span::from(Self::ASCIIMAC[5]).white(),
+                Span::from("MIPS   ").style(Style::default().blue().bold()),
+                Span::from(format!("{:>14.2}", self.emustatus.ips as f64 / 1_000_000.0))
+                    .style(Style::default().white()),
             ]),
-            Line::from(Self::ASCIIMAC[5]).white(),
         ])
         .block(Block::bordered().title(self.emustatus.model.to_string()))
         .render(layout[0], buf);